@@ -0,0 +1,748 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{stemmer, stopwords};
+use super::synonyms::SynonymMap;
+
+/// A term produced by analysis.
+///
+/// `position` counts tokens, not characters: filters that drop tokens leave
+/// gaps behind and tokens stacked on the same position (synonyms, n-grams of
+/// different sizes) are alternatives for each other. `length` is the number
+/// of positions the token spans, more than one for a word standing in for a
+/// multi-word synonym. `start` and `end` are the byte range of the token in
+/// the original text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub text: String,
+    pub position: usize,
+    pub length: usize,
+    pub start: usize,
+    pub end: usize
+}
+
+impl Token {
+    pub fn new(text: &str, position: usize, start: usize, end: usize) -> Self {
+        Self { text: text.to_string(), position, length: 1, start, end }
+    }
+}
+
+/// Splits text into tokens.
+pub trait Tokenizer: Debug + Send + Sync {
+    fn tokenize(&self, text: &str) -> Vec<Token>;
+}
+
+/// Rewrites a token stream: changes, drops or adds tokens.
+pub trait TokenFilter: Debug + Send + Sync {
+    fn filter(&self, tokens: Vec<Token>) -> Vec<Token>;
+
+    /// Applies the part of the filter that makes sense for a single term of a
+    /// prefix, wildcard or fuzzy query, such as lowercasing. Filters that
+    /// change terms in other ways (stemming, dropping words) leave it alone.
+    fn normalize(&self, term: String) -> String {
+        term
+    }
+}
+
+/// Turns text into the terms that are indexed or searched for. The same
+/// analyzer has to be used for indexing and querying.
+pub trait Analyzer: Debug + Send + Sync {
+    fn analyze(&self, text: &str) -> Vec<Token>;
+
+    /// Normalizes a single term of a multi-term query, see `TokenFilter::normalize`.
+    fn normalize(&self, term: &str) -> String {
+        term.to_lowercase()
+    }
+}
+
+/// An analyzer built from a tokenizer followed by a chain of token filters.
+#[derive(Debug)]
+pub struct TextAnalyzer {
+    tokenizer: Box<dyn Tokenizer>,
+    filters: Vec<Box<dyn TokenFilter>>
+}
+
+impl TextAnalyzer {
+    pub fn new(tokenizer: impl Tokenizer + 'static) -> Self {
+        Self { tokenizer: Box::new(tokenizer), filters: Vec::new() }
+    }
+
+    /// Appends a filter to the chain.
+    pub fn filter(mut self, filter: impl TokenFilter + 'static) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+}
+
+impl TextAnalyzer {
+    /// Unicode words, lowercased, without the stop words of `language`,
+    /// folded to ASCII and stemmed for it. The stop words are written with
+    /// their accents, so they go before folding; the stemmer comes after so
+    /// words typed with and without accents end up on the same stem.
+    pub fn for_language(language: Language) -> Self {
+        TextAnalyzer::new(UnicodeWordTokenizer)
+            .filter(NormalizationFilter::nfc())
+            .filter(LowercaseFilter)
+            .filter(StopWordFilter::for_language(language))
+            .filter(AsciiFoldingFilter)
+            .filter(StemmingFilter::new(language))
+    }
+}
+
+impl Default for TextAnalyzer {
+    /// Unicode words in NFC, lowercased and folded to ASCII.
+    fn default() -> Self {
+        TextAnalyzer::new(UnicodeWordTokenizer).filter(NormalizationFilter::nfc()).filter(LowercaseFilter).filter(AsciiFoldingFilter)
+    }
+}
+
+impl Analyzer for TextAnalyzer {
+    fn analyze(&self, text: &str) -> Vec<Token> {
+        let tokens = self.tokenizer.tokenize(text);
+        self.filters.iter().fold(tokens, |tokens, filter| filter.filter(tokens))
+    }
+
+    fn normalize(&self, term: &str) -> String {
+        self.filters.iter().fold(term.to_string(), |term, filter| filter.normalize(term))
+    }
+}
+
+/// Analyzers by name, so a schema can refer to them. It starts out with
+/// `standard` (the default analyzer), `english`, `french`, `german` and
+/// `spanish` (stop words and stemming), `cjk`, `ngram` (2 to 3 characters),
+/// `edge_ngram` (2 to 20 characters), `code` (identifiers split into words)
+/// and `keyword` (the whole value as one term).
+#[derive(Debug, Clone)]
+pub struct AnalyzerRegistry {
+    analyzers: HashMap<String, Arc<dyn Analyzer>>
+}
+
+impl Default for AnalyzerRegistry {
+    fn default() -> Self {
+        let mut registry = Self { analyzers: HashMap::new() };
+        registry.register("standard", TextAnalyzer::default());
+        registry.register("english", TextAnalyzer::for_language(Language::English));
+        registry.register("french", TextAnalyzer::for_language(Language::French));
+        registry.register("german", TextAnalyzer::for_language(Language::German));
+        registry.register("spanish", TextAnalyzer::for_language(Language::Spanish));
+        registry.register("cjk", TextAnalyzer::new(CjkBigramTokenizer::new(true)).filter(NormalizationFilter::nfkc()).filter(LowercaseFilter));
+        registry.register("ngram", TextAnalyzer::new(NGramTokenizer::new(2, 3)).filter(LowercaseFilter));
+        registry.register("edge_ngram", TextAnalyzer::new(EdgeNGramTokenizer::new(2, 20)).filter(LowercaseFilter));
+        registry.register("code", TextAnalyzer::new(UnicodeWordTokenizer).filter(WordDelimiterFilter::new(true)).filter(LowercaseFilter));
+        registry.register("keyword", TextAnalyzer::new(KeywordTokenizer));
+        registry
+    }
+}
+
+impl AnalyzerRegistry {
+    /// Adds an analyzer, replacing any registered under the same name.
+    pub fn register(&mut self, name: &str, analyzer: impl Analyzer + 'static) {
+        self.analyzers.insert(name.to_string(), Arc::new(analyzer));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn Analyzer>> {
+        self.analyzers.get(name)
+    }
+}
+
+/// The whole input, trimmed, as a single token.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeywordTokenizer;
+
+impl Tokenizer for KeywordTokenizer {
+    fn tokenize(&self, text: &str) -> Vec<Token> {
+        let trimmed = text.trim();
+        if trimmed.is_empty() {
+            return vec![]
+        }
+        let start = text.len() - text.trim_start().len();
+        vec![Token::new(trimmed, 0, start, start + trimmed.len())]
+    }
+}
+
+/// Splits on Unicode word boundaries (UAX #29), dropping punctuation and spaces.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UnicodeWordTokenizer;
+
+impl Tokenizer for UnicodeWordTokenizer {
+    fn tokenize(&self, text: &str) -> Vec<Token> {
+        text.unicode_word_indices()
+            .enumerate()
+            .map(|(position, (start, word))| Token::new(word, position, start, start + word.len()))
+            .collect()
+    }
+}
+
+/// Cuts every word into the substrings of `min_gram` to `max_gram` characters,
+/// for searching inside words. A gram is placed at its character offset in
+/// the word, so a query run through the same tokenizer becomes a phrase of
+/// grams that matches wherever the query is a substring. Words shorter than
+/// `min_gram` are kept whole.
+#[derive(Debug, Clone, Copy)]
+pub struct NGramTokenizer {
+    min_gram: usize,
+    max_gram: usize
+}
+
+impl NGramTokenizer {
+    pub fn new(min_gram: usize, max_gram: usize) -> Self {
+        let min_gram = min_gram.max(1);
+        Self { min_gram, max_gram: max_gram.max(min_gram) }
+    }
+}
+
+impl Tokenizer for NGramTokenizer {
+    fn tokenize(&self, text: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        // one position is left empty between words so grams never line up across them
+        let mut base = 0;
+        for (start, word) in text.unicode_word_indices() {
+            let offsets = char_offsets(word);
+            let chars = offsets.len() - 1;
+            if chars < self.min_gram {
+                tokens.push(Token::new(word, base, start, start + word.len()));
+            }
+            for first in 0..chars {
+                for last in (first + self.min_gram..=first + self.max_gram).take_while(|&last| last <= chars) {
+                    let (from, to) = (offsets[first], offsets[last]);
+                    tokens.push(Token::new(&word[from..to], base + first, start + from, start + to));
+                }
+            }
+            base += chars + 1;
+        }
+        tokens
+    }
+}
+
+/// Cuts every word into its prefixes of `min_gram` to `max_gram` characters,
+/// for search-as-you-type. Longer prefixes take later positions, so a query
+/// run through the same tokenizer matches the words it is a prefix of. Words
+/// shorter than `min_gram` are kept whole.
+#[derive(Debug, Clone, Copy)]
+pub struct EdgeNGramTokenizer {
+    min_gram: usize,
+    max_gram: usize
+}
+
+impl EdgeNGramTokenizer {
+    pub fn new(min_gram: usize, max_gram: usize) -> Self {
+        let min_gram = min_gram.max(1);
+        Self { min_gram, max_gram: max_gram.max(min_gram) }
+    }
+}
+
+impl Tokenizer for EdgeNGramTokenizer {
+    fn tokenize(&self, text: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let mut base = 0;
+        for (start, word) in text.unicode_word_indices() {
+            let offsets = char_offsets(word);
+            let chars = offsets.len() - 1;
+            if chars < self.min_gram {
+                tokens.push(Token::new(word, base, start, start + word.len()));
+            }
+            for len in self.min_gram..=self.max_gram.min(chars) {
+                tokens.push(Token::new(&word[..offsets[len]], base + len - self.min_gram, start, start + offsets[len]));
+            }
+            // every word gets the same number of positions, however long it is
+            base += self.max_gram - self.min_gram + 2;
+        }
+        tokens
+    }
+}
+
+/// Tokenizes Chinese, Japanese and Korean text, which is not written with
+/// spaces between words, into overlapping bigrams: `東京都` gives `東京` and
+/// `京都`. A query for a longer word becomes a phrase of its bigrams. With
+/// `output_unigrams` the single characters are indexed as well, stacked on the
+/// bigram starting with them, so one-character queries match too. Runs of
+/// other scripts are split on Unicode words.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CjkBigramTokenizer {
+    pub output_unigrams: bool
+}
+
+impl CjkBigramTokenizer {
+    pub fn new(output_unigrams: bool) -> Self {
+        Self { output_unigrams }
+    }
+
+    fn is_cjk(c: char) -> bool {
+        matches!(c as u32,
+            0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x2EBEF // han
+            | 0x3040..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9F // kana
+            | 0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF) // hangul
+    }
+
+    /// Emits the bigrams of a run of CJK characters, given with their byte offsets.
+    fn flush(&self, run: &mut Vec<(usize, char)>, base: &mut usize, tokens: &mut Vec<Token>) {
+        let end = |(start, c): (usize, char)| start + c.len_utf8();
+        let text = |from: usize, to: usize| run[from..to].iter().map(|&(_, c)| c).collect::<String>();
+        if run.len() == 1 {
+            tokens.push(Token::new(&text(0, 1), *base, run[0].0, end(run[0])));
+        } else {
+            for i in 0..run.len() {
+                if self.output_unigrams {
+                    tokens.push(Token::new(&text(i, i + 1), *base + i, run[i].0, end(run[i])));
+                }
+                if i + 1 < run.len() {
+                    tokens.push(Token::new(&text(i, i + 2), *base + i, run[i].0, end(run[i + 1])));
+                }
+            }
+        }
+        *base += run.len();
+        run.clear();
+    }
+}
+
+impl Tokenizer for CjkBigramTokenizer {
+    fn tokenize(&self, text: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let mut run: Vec<(usize, char)> = Vec::new();
+        let mut base = 0;
+        for (start, word) in text.unicode_word_indices() {
+            if word.chars().all(Self::is_cjk) {
+                // characters only form one run while nothing separates them
+                if run.last().is_some_and(|&(last, c)| last + c.len_utf8() != start) {
+                    self.flush(&mut run, &mut base, &mut tokens);
+                }
+                run.extend(word.char_indices().map(|(idx, c)| (start + idx, c)));
+                continue
+            }
+            if !run.is_empty() {
+                self.flush(&mut run, &mut base, &mut tokens);
+            }
+            tokens.push(Token::new(word, base, start, start + word.len()));
+            base += 1;
+        }
+        if !run.is_empty() {
+            self.flush(&mut run, &mut base, &mut tokens);
+        }
+        tokens
+    }
+}
+
+/// Byte offset of every char boundary of `word`, the end included.
+fn char_offsets(word: &str) -> Vec<usize> {
+    word.char_indices().map(|(idx, _)| idx).chain(std::iter::once(word.len())).collect()
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LowercaseFilter;
+
+impl TokenFilter for LowercaseFilter {
+    fn filter(&self, tokens: Vec<Token>) -> Vec<Token> {
+        tokens.into_iter().map(|token| Token { text: token.text.to_lowercase(), ..token }).collect()
+    }
+
+    fn normalize(&self, term: String) -> String {
+        term.to_lowercase()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    English,
+    French,
+    German,
+    Spanish
+}
+
+/// Reduces lowercased terms to their stem, so `running`, `runs` and `run`
+/// become the same term.
+#[derive(Debug, Clone, Copy)]
+pub struct StemmingFilter {
+    language: Language
+}
+
+impl StemmingFilter {
+    pub fn new(language: Language) -> Self {
+        Self { language }
+    }
+}
+
+impl TokenFilter for StemmingFilter {
+    fn filter(&self, tokens: Vec<Token>) -> Vec<Token> {
+        tokens.into_iter().map(|token| Token { text: stemmer::stem(self.language, &token.text), ..token }).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalizationForm {
+    /// Canonical composition: `e` followed by a combining acute becomes `é`.
+    Nfc,
+    /// Compatibility composition, which also folds ligatures, full-width
+    /// forms and the like: `ﬁ` becomes `fi`, `Ａ` becomes `A`.
+    Nfkc
+}
+
+/// Brings terms to one Unicode normalization form, so text written with
+/// composed and decomposed characters gives the same terms.
+#[derive(Debug, Clone, Copy)]
+pub struct NormalizationFilter {
+    form: NormalizationForm
+}
+
+impl NormalizationFilter {
+    pub fn new(form: NormalizationForm) -> Self {
+        Self { form }
+    }
+
+    pub fn nfc() -> Self {
+        Self::new(NormalizationForm::Nfc)
+    }
+
+    pub fn nfkc() -> Self {
+        Self::new(NormalizationForm::Nfkc)
+    }
+}
+
+impl TokenFilter for NormalizationFilter {
+    fn filter(&self, tokens: Vec<Token>) -> Vec<Token> {
+        tokens.into_iter().map(|token| Token { text: self.normalize(token.text), ..token }).collect()
+    }
+
+    fn normalize(&self, term: String) -> String {
+        match self.form {
+            NormalizationForm::Nfc => term.nfc().collect(),
+            NormalizationForm::Nfkc => term.nfkc().collect()
+        }
+    }
+}
+
+/// Folds letters to their ASCII base, `café` to `cafe` and `straße` to
+/// `strasse`, by stripping diacritics and spelling out the letters that do
+/// not decompose. Characters without an ASCII form are left alone.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsciiFoldingFilter;
+
+impl AsciiFoldingFilter {
+    fn fold(c: char) -> Option<&'static str> {
+        Some(match c {
+            'ß' => "ss",
+            'æ' => "ae",
+            'Æ' => "AE",
+            'œ' => "oe",
+            'Œ' => "OE",
+            'ø' => "o",
+            'Ø' => "O",
+            'đ' | 'ð' => "d",
+            'Đ' | 'Ð' => "D",
+            'ł' => "l",
+            'Ł' => "L",
+            'þ' => "th",
+            'Þ' => "TH",
+            'ı' => "i",
+            'ħ' => "h",
+            'ŧ' => "t",
+            _ => return None
+        })
+    }
+}
+
+impl TokenFilter for AsciiFoldingFilter {
+    fn filter(&self, tokens: Vec<Token>) -> Vec<Token> {
+        tokens.into_iter().map(|token| Token { text: self.normalize(token.text), ..token }).collect()
+    }
+
+    fn normalize(&self, term: String) -> String {
+        if term.is_ascii() {
+            return term
+        }
+        let mut folded = String::with_capacity(term.len());
+        for c in term.nfkd().filter(|&c| !is_combining_mark(c)) {
+            match Self::fold(c) {
+                Some(ascii) => folded.push_str(ascii),
+                None => folded.push(c)
+            }
+        }
+        folded
+    }
+}
+
+/// Splits identifiers into their words: `PageCacheManager` into `Page`,
+/// `Cache`, `Manager`, `page_cache` into `page`, `cache`. The parts take
+/// consecutive positions and the tokens after them move back accordingly, so
+/// `"page cache"` matches as a phrase. It looks at case, so it goes before
+/// lowercasing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WordDelimiterFilter {
+    /// Also keeps the whole identifier, on the position of its first part.
+    pub preserve_original: bool
+}
+
+impl WordDelimiterFilter {
+    pub fn new(preserve_original: bool) -> Self {
+        Self { preserve_original }
+    }
+
+    /// Byte ranges of the parts of `text`.
+    fn parts(text: &str) -> Vec<(usize, usize)> {
+        let chars: Vec<(usize, char)> = text.char_indices().collect();
+        let mut parts = Vec::new();
+        let mut start = None;
+        for (k, &(idx, c)) in chars.iter().enumerate() {
+            if matches!(c, '_' | '-' | '.') {
+                if let Some(start) = start.take() {
+                    parts.push((start, idx));
+                }
+                continue
+            }
+            let Some(from) = start else {
+                start = Some(idx);
+                continue
+            };
+            // pageCache, and HTTPServer where the S starts the next word
+            let prev = chars[k - 1].1;
+            let next_lower = chars.get(k + 1).is_some_and(|&(_, next)| next.is_lowercase());
+            if c.is_uppercase() && (prev.is_lowercase() || (prev.is_uppercase() && next_lower)) {
+                parts.push((from, idx));
+                start = Some(idx);
+            }
+        }
+        if let Some(start) = start {
+            parts.push((start, text.len()));
+        }
+        parts
+    }
+}
+
+impl TokenFilter for WordDelimiterFilter {
+    fn filter(&self, tokens: Vec<Token>) -> Vec<Token> {
+        let mut filtered = Vec::with_capacity(tokens.len());
+        let mut shift = 0;
+        for token in tokens {
+            let parts = Self::parts(&token.text);
+            let position = token.position + shift;
+            if parts.len() <= 1 {
+                let text = parts.first().map_or(token.text.as_str(), |&(from, to)| &token.text[from..to]);
+                filtered.push(Token::new(text, position, token.start, token.end));
+                continue
+            }
+            if self.preserve_original {
+                filtered.push(Token { position, ..token.clone() });
+            }
+            for (offset, &(from, to)) in parts.iter().enumerate() {
+                let (start, end) = ((token.start + from).min(token.end), (token.start + to).min(token.end));
+                filtered.push(Token::new(&token.text[from..to], position + offset, start, end));
+            }
+            shift += parts.len() - 1;
+        }
+        filtered
+    }
+}
+
+/// Drops stop words. The positions of the remaining tokens are kept, so a
+/// phrase still only matches words the same distance apart.
+#[derive(Debug, Clone)]
+pub struct StopWordFilter {
+    words: HashSet<String>
+}
+
+impl StopWordFilter {
+    pub fn new<S: Into<String>>(words: impl IntoIterator<Item = S>) -> Self {
+        Self { words: words.into_iter().map(Into::into).collect() }
+    }
+
+    /// The built-in list for `language`.
+    pub fn for_language(language: Language) -> Self {
+        Self::new(stopwords::words(language).iter().copied())
+    }
+
+    /// Reads a list with one word per line. Blank lines and lines starting
+    /// with `#` are skipped; words are matched as written, so the filter has
+    /// to come after any filter that changes them, such as lowercasing.
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(Self::new(content.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'))))
+    }
+}
+
+impl TokenFilter for StopWordFilter {
+    fn filter(&self, tokens: Vec<Token>) -> Vec<Token> {
+        tokens.into_iter().filter(|token| !self.words.contains(&token.text)).collect()
+    }
+}
+
+/// Adds or substitutes synonyms. Single-word synonyms are stacked on the
+/// position of the word they replace. A multi-word synonym takes one position
+/// per word and moves the tokens after it by the difference with the matched
+/// words, so phrases keep matching as long as documents and queries go
+/// through the same filter. The last word of a shorter alternative spans the
+/// positions left over, which lets a query take each alternative on its own.
+#[derive(Debug, Clone)]
+pub struct SynonymFilter {
+    map: SynonymMap,
+    preserve_original: bool
+}
+
+impl SynonymFilter {
+    pub fn new(map: SynonymMap) -> Self {
+        Self { map, preserve_original: false }
+    }
+
+    /// Keeps the matched words next to what an explicit mapping replaces
+    /// them with. Meant for query analyzers, so documents indexed without
+    /// the synonyms are still found by the words they contain.
+    pub fn preserve_original(mut self, preserve_original: bool) -> Self {
+        self.preserve_original = preserve_original;
+        self
+    }
+
+    /// Reads rules from a Solr synonyms file, see `SynonymMap`.
+    pub fn from_file(path: impl AsRef<Path>, expand: bool) -> io::Result<Self> {
+        Ok(Self::new(SynonymMap::from_file(path, expand)?))
+    }
+}
+
+impl TokenFilter for SynonymFilter {
+    fn filter(&self, tokens: Vec<Token>) -> Vec<Token> {
+        let mut filtered = Vec::with_capacity(tokens.len());
+        let mut shift: isize = 0;
+        let shifted = |position: usize, shift: isize| (position as isize + shift) as usize;
+        let mut i = 0;
+        while i < tokens.len() {
+            // longest rule matching the consecutive tokens starting here
+            let rule = (1..=self.map.longest().min(tokens.len() - i)).rev().find_map(|n| {
+                let run = &tokens[i..i + n];
+                if run.windows(2).any(|pair| pair[1].position != pair[0].position + 1) {
+                    return None
+                }
+                let words: Vec<String> = run.iter().map(|token| token.text.clone()).collect();
+                self.map.get(&words).map(|outputs| (run, outputs))
+            });
+            let Some((run, outputs)) = rule else {
+                filtered.push(Token { position: shifted(tokens[i].position, shift), ..tokens[i].clone() });
+                i += 1;
+                continue
+            };
+            let n = run.len();
+            let words: Vec<String> = run.iter().map(|token| token.text.clone()).collect();
+            let mut outputs: Vec<&[String]> = outputs.iter().map(Vec::as_slice).collect();
+            if self.preserve_original && !outputs.contains(&words.as_slice()) {
+                outputs.insert(0, &words);
+            }
+            let position = shifted(run[0].position, shift);
+            let span = outputs.iter().map(|output| output.len()).max().unwrap_or(n);
+            for output in &outputs {
+                for (offset, word) in output.iter().enumerate() {
+                    // words line up with the matched ones when the counts agree
+                    let (start, end) = if output.len() == n { (run[offset].start, run[offset].end) } else { (run[0].start, run[n - 1].end) };
+                    let length = if offset + 1 == output.len() { span - output.len() + 1 } else { 1 };
+                    filtered.push(Token { length, ..Token::new(word, position + offset, start, end) });
+                }
+            }
+            shift += span as isize - n as isize;
+            i += n;
+        }
+        filtered.sort_by_key(|token| token.position);
+        filtered
+    }
+}
+
+/// Most ways through a token stream `token_paths` gives back.
+const MAX_TOKEN_PATHS: usize = 16;
+
+/// The ways through a token stream from `position` to `end`, taking either
+/// the tokens stacked on a position or one token spanning several of them at
+/// each step. A way is a list of terms with their positions counted from the
+/// first one, as the words would come one after the other in a text; gaps
+/// left by dropped words are kept.
+pub fn token_paths(tokens: &[Token], position: usize, end: usize) -> Vec<Vec<(String, usize)>> {
+    if position >= end {
+        return vec![vec![]]
+    }
+    let here = || tokens.iter().filter(|token| token.position == position);
+    let stacked: Vec<&Token> = here().filter(|token| token.length == 1).collect();
+    let mut steps: Vec<(Vec<&Token>, usize)> = here().filter(|token| token.length > 1).map(|token| (vec![token], token.length)).collect();
+    if !stacked.is_empty() || steps.is_empty() {
+        steps.push((stacked, 1));
+    }
+    let mut paths = Vec::new();
+    for (step, length) in steps {
+        for rest in token_paths(tokens, position + length, end) {
+            let mut path: Vec<(String, usize)> = step.iter().map(|token| (token.text.clone(), 0)).collect();
+            path.extend(rest.into_iter().map(|(text, offset)| (text, offset + 1)));
+            paths.push(path);
+        }
+    }
+    paths.truncate(MAX_TOKEN_PATHS);
+    paths
+}
+
+/// Number of distinct positions in a token stream, used as the document length.
+pub fn token_count(tokens: &[Token]) -> usize {
+    let mut positions: Vec<usize> = tokens.iter().map(|token| token.position).collect();
+    positions.sort_unstable();
+    positions.dedup();
+    positions.len()
+}
+
+#[test]
+pub fn test_default_analyzer() {
+    let analyzer = TextAnalyzer::default();
+    let tokens = analyzer.analyze("Rust is a Systems language.");
+    let terms: Vec<&str> = tokens.iter().map(|token| token.text.as_str()).collect();
+    assert_eq!(terms, vec!["rust", "is", "a", "systems", "language"]);
+    assert_eq!(tokens[3], Token::new("systems", 3, 10, 17));
+    assert_eq!(analyzer.normalize("OPTIM"), "optim");
+    assert_eq!(token_count(&tokens), 5);
+
+    let english = TextAnalyzer::for_language(Language::English);
+    let stems: Vec<(String, usize)> = english.analyze("Running is the art of runs").into_iter().map(|token| (token.text, token.position)).collect();
+    assert_eq!(stems, vec![("run".to_string(), 0), ("art".to_string(), 3), ("run".to_string(), 5)]);
+}
+
+#[test]
+pub fn test_normalization() {
+    let texts = |analyzer: &TextAnalyzer, text: &str| -> Vec<String> { analyzer.analyze(text).into_iter().map(|token| token.text).collect() };
+    // composed and decomposed é
+    let analyzer = TextAnalyzer::default();
+    assert_eq!(texts(&analyzer, "caf\u{e9}"), texts(&analyzer, "cafe\u{301}"));
+    let nfkc = TextAnalyzer::new(UnicodeWordTokenizer).filter(NormalizationFilter::nfkc()).filter(LowercaseFilter);
+    assert_eq!(texts(&nfkc, "\u{fb01}le ＡＢＣ"), vec!["file", "abc"]);
+
+    let folding = TextAnalyzer::default();
+    assert_eq!(texts(&folding, "Café Straße naïve Łódź"), vec!["cafe", "strasse", "naive", "lodz"]);
+    assert_eq!(folding.normalize("Crème*"), "creme*");
+    // stop words are matched before they lose their accents
+    let french = TextAnalyzer::for_language(Language::French);
+    assert_eq!(texts(&french, "été à café"), texts(&french, "cafe"));
+}
+
+#[test]
+pub fn test_cjk_bigrams() {
+    let texts = |tokens: Vec<Token>| -> Vec<(String, usize)> { tokens.into_iter().map(|token| (token.text, token.position)).collect() };
+    let bigrams = texts(CjkBigramTokenizer::default().tokenize("東京都に住む Rust"));
+    let expected = [("東京", 0), ("京都", 1), ("都に", 2), ("に住", 3), ("住む", 4), ("Rust", 6)];
+    assert_eq!(bigrams, expected.map(|(text, position)| (text.to_string(), position)));
+
+    let tokens = CjkBigramTokenizer::new(true).tokenize("日本、語");
+    let expected = [("日", 0), ("日本", 0), ("本", 1), ("語", 2)];
+    assert_eq!(texts(tokens.clone()), expected.map(|(text, position)| (text.to_string(), position)));
+    assert_eq!((tokens[1].start, tokens[1].end), (0, 6));
+}
+
+#[test]
+pub fn test_ngrams() {
+    let texts = |tokens: Vec<Token>| -> Vec<(String, usize)> { tokens.into_iter().map(|token| (token.text, token.position)).collect() };
+    let grams = texts(NGramTokenizer::new(2, 3).tokenize("page a"));
+    let expected = [("pa", 0), ("pag", 0), ("ag", 1), ("age", 1), ("ge", 2), ("a", 5)];
+    assert_eq!(grams, expected.map(|(text, position)| (text.to_string(), position)));
+
+    let prefixes = texts(EdgeNGramTokenizer::new(2, 4).tokenize("cache ok"));
+    let expected = [("ca", 0), ("cac", 1), ("cach", 2), ("ok", 4)];
+    assert_eq!(prefixes, expected.map(|(text, position)| (text.to_string(), position)));
+
+    let analyzer = TextAnalyzer::new(UnicodeWordTokenizer).filter(WordDelimiterFilter::default()).filter(LowercaseFilter);
+    let words = texts(analyzer.analyze("PageCacheManager reads HTTPServer page_cache"));
+    let expected = [("page", 0), ("cache", 1), ("manager", 2), ("reads", 3), ("http", 4), ("server", 5), ("page", 6), ("cache", 7)];
+    assert_eq!(words, expected.map(|(text, position)| (text.to_string(), position)));
+}
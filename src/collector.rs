@@ -0,0 +1,107 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::path::PathBuf;
+
+use super::DocumentId;
+
+/// Where the previous page ended: results at or before it are skipped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cursor {
+    pub score: f32,
+    pub doc_id: DocumentId
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    pub doc_id: DocumentId,
+    pub path: PathBuf,
+    pub score: f32
+}
+
+impl Hit {
+    /// The cursor to pass as `after` to fetch the page following this hit.
+    pub fn cursor(&self) -> Cursor {
+        Cursor { score: self.score, doc_id: self.doc_id }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchPage {
+    /// How many documents matched the query in total.
+    pub total: usize,
+    pub hits: Vec<Hit>
+}
+
+/// A scored document ordered by rank: higher scores first, then lower ids.
+#[derive(Debug, Clone, Copy)]
+struct Ranked(f32, DocumentId);
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked {}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked {
+    // `Greater` means ranked better
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(other.1.cmp(&self.1))
+    }
+}
+
+/// Keeps the best `k` documents seen, after an optional cursor, in a bounded
+/// min-heap so memory stays at `k` however many documents are collected.
+#[derive(Debug)]
+pub struct TopDocs {
+    k: usize,
+    after: Option<Ranked>,
+    heap: BinaryHeap<Reverse<Ranked>>
+}
+
+impl TopDocs {
+    pub fn new(k: usize, after: Option<Cursor>) -> Self {
+        Self { k, after: after.map(|cursor| Ranked(cursor.score, cursor.doc_id)), heap: BinaryHeap::with_capacity(k + 1) }
+    }
+
+    pub fn collect(&mut self, doc_id: DocumentId, score: f32) {
+        let ranked = Ranked(score, doc_id);
+        if self.k == 0 || self.after.is_some_and(|after| ranked >= after) {
+            return
+        }
+        if self.heap.len() < self.k {
+            self.heap.push(Reverse(ranked));
+        } else if self.heap.peek().is_some_and(|Reverse(worst)| ranked > *worst) {
+            self.heap.pop();
+            self.heap.push(Reverse(ranked));
+        }
+    }
+
+    /// The collected documents, best first.
+    pub fn into_sorted(self) -> Vec<(DocumentId, f32)> {
+        self.heap.into_sorted_vec().into_iter().map(|Reverse(Ranked(score, doc_id))| (doc_id, score)).collect()
+    }
+}
+
+#[test]
+pub fn test_top_docs() {
+    let scores = [(1, 0.5), (2, 2.0), (3, 1.0), (4, 2.0), (5, 0.1), (6, 1.0)];
+    let mut top = TopDocs::new(3, None);
+    for (doc, score) in scores {
+        top.collect(doc, score);
+    }
+    assert_eq!(top.into_sorted(), vec![(2, 2.0), (4, 2.0), (3, 1.0)]);
+
+    let mut next = TopDocs::new(3, Some(Cursor { score: 1.0, doc_id: 3 }));
+    for (doc, score) in scores {
+        next.collect(doc, score);
+    }
+    assert_eq!(next.into_sorted(), vec![(6, 1.0), (1, 0.5), (5, 0.1)]);
+}
@@ -0,0 +1,147 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::fs::File;
+use std::io::{self, Error, ErrorKind, Read};
+use std::ops::Bound;
+use std::path::Path;
+
+use bincode::{Decode, Encode};
+
+use super::atomic_write;
+
+/// The sorted term dictionary, mapping every indexed term to the number of
+/// documents it occurs in.
+///
+/// It is rebuilt from the segment store whenever `entries` or `removed` no
+/// longer agree with it, so a crash before `save` only costs a rebuild on the
+/// next start.
+#[derive(Encode, Decode, Debug, Default, Clone)]
+pub struct TermDictionary {
+    terms: BTreeMap<String, usize>,
+    // postings chunks the dictionary accounts for
+    entries: usize,
+    // deleted documents whose terms were taken out
+    removed: usize
+}
+
+impl TermDictionary {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut buf = Vec::new();
+        File::open(path)?.read_to_end(&mut buf)?;
+        bincode::decode_from_slice(&buf, bincode::config::standard())
+            .map(|(dictionary, _)| dictionary)
+            .map_err(|err| Error::new(ErrorKind::InvalidData, format!("can not decode term dictionary: {err}")))
+    }
+
+    /// Writes the dictionary to `path`, see `atomic_write`.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let bytes = bincode::encode_to_vec(self, bincode::config::standard())
+            .map_err(|err| Error::new(ErrorKind::InvalidData, format!("can not encode term dictionary: {err}")))?;
+        atomic_write(path, &bytes)
+    }
+
+    /// Counts `doc_freq` more documents for `term`, whose postings took
+    /// `chunks` chunks in the segments.
+    pub fn insert(&mut self, term: &str, doc_freq: usize, chunks: usize) {
+        if doc_freq > 0 {
+            *self.terms.entry(term.to_string()).or_default() += doc_freq;
+        }
+        self.entries += chunks;
+    }
+
+    /// Takes one deleted document out of the counts of its `terms`, dropping
+    /// the terms no other document has.
+    pub fn remove_document(&mut self, terms: &[String]) {
+        for term in terms {
+            if let Some(doc_freq) = self.terms.get_mut(term) {
+                *doc_freq -= 1;
+                if *doc_freq == 0 {
+                    self.terms.remove(term);
+                }
+            }
+        }
+        self.removed += 1;
+    }
+
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+
+    pub fn removed(&self) -> usize {
+        self.removed
+    }
+
+    /// Counts `removed` deleted documents as taken out already, for a
+    /// dictionary built from postings that leave them out.
+    pub fn set_removed(&mut self, removed: usize) {
+        self.removed = removed;
+    }
+
+    pub fn len(&self) -> usize {
+        self.terms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub fn doc_freq(&self, term: &str) -> usize {
+        self.terms.get(term).copied().unwrap_or(0)
+    }
+
+    /// All terms in sorted order.
+    pub fn terms(&self) -> impl Iterator<Item = &str> {
+        self.terms.keys().map(String::as_str)
+    }
+
+    /// The terms starting with `prefix`, in sorted order.
+    pub fn with_prefix<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = (&'a str, usize)> {
+        self.terms.range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(move |(term, _)| term.starts_with(prefix))
+            .map(|(term, doc_freq)| (term.as_str(), *doc_freq))
+    }
+
+    /// The `n` most frequent terms starting with `prefix`.
+    pub fn complete(&self, prefix: &str, n: usize) -> Vec<(String, usize)> {
+        self.complete_where(prefix, n, |_| true)
+    }
+
+    /// Like `complete`, but only considers the terms `keep` accepts.
+    pub fn complete_where(&self, prefix: &str, n: usize, keep: impl Fn(&str) -> bool) -> Vec<(String, usize)> {
+        if n == 0 {
+            return vec![]
+        }
+        // min-heap of the best n so far; ties go to the alphabetically first term
+        let mut heap = BinaryHeap::with_capacity(n + 1);
+        for (term, doc_freq) in self.with_prefix(prefix).filter(|(term, _)| keep(term)) {
+            heap.push(Reverse((doc_freq, Reverse(term))));
+            if heap.len() > n {
+                heap.pop();
+            }
+        }
+        heap.into_sorted_vec().into_iter()
+            .map(|Reverse((doc_freq, Reverse(term)))| (term.to_string(), doc_freq))
+            .collect()
+    }
+}
+
+#[test]
+pub fn test_complete() {
+    let mut dictionary = TermDictionary::default();
+    for (term, doc_freq) in [("optics", 1), ("optimal", 4), ("optimize", 9), ("optimizer", 4), ("option", 2), ("rust", 20)] {
+        dictionary.insert(term, doc_freq, 1);
+    }
+    // a document written as several chunks still counts once
+    dictionary.insert("optics", 1, 3);
+    assert_eq!(dictionary.complete("optim", 2), vec![("optimize".to_string(), 9), ("optimal".to_string(), 4)]);
+    assert_eq!(dictionary.doc_freq("optics"), 2);
+    assert_eq!(dictionary.complete("opt", 10).len(), 5);
+    assert!(dictionary.complete("x", 3).is_empty());
+    assert_eq!(dictionary.entries(), 9);
+
+    dictionary.remove_document(&["optics".to_string(), "option".to_string()]);
+    dictionary.remove_document(&["option".to_string()]);
+    assert_eq!(dictionary.doc_freq("optics"), 1);
+    assert_eq!(dictionary.complete("opti", 10).len(), 4);
+    assert_eq!(dictionary.removed(), 2);
+}
@@ -0,0 +1,113 @@
+use std::path::{Path, PathBuf};
+
+use bincode::{Decode, Encode};
+
+use super::{DEFAULT_FIELD, DocumentId};
+
+/// Positions left empty between the values of a multi-valued field, so a
+/// phrase never matches across two values.
+pub const VALUE_POSITION_GAP: usize = 100;
+
+/// A document made of named text fields. A field may be given several times,
+/// for multi-valued fields like tags.
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Default)]
+pub struct Document {
+    pub path: PathBuf,
+    pub fields: Vec<(String, String)>
+}
+
+impl Document {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), fields: Vec::new() }
+    }
+
+    /// Adds a value to a field.
+    pub fn field(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.fields.push((name.to_string(), value.into()));
+        self
+    }
+
+    /// The values of a field, in the order they were added.
+    pub fn values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.fields.iter().filter(move |(field, _)| field == name).map(|(_, value)| value.as_str())
+    }
+
+    /// FNV-1a hash of the fields, stable across runs, to tell whether a
+    /// document changed since it was indexed.
+    pub fn content_hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for (name, value) in &self.fields {
+            // 0xff never occurs in UTF-8, so field boundaries can not shift
+            for byte in name.bytes().chain([0xff]).chain(value.bytes()).chain([0xff]) {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        }
+        hash
+    }
+}
+
+/// What `InvertedIndex::update_document` did with a document.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Update {
+    /// The path was not indexed yet.
+    Added(DocumentId),
+    /// The content is the same as when it was indexed, nothing was written.
+    Unchanged(DocumentId),
+    /// The document was indexed again under `new` and `old` deleted.
+    Replaced { old: DocumentId, new: DocumentId }
+}
+
+/// Names an indexed document, by its id or by the path it was added with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocumentRef<'a> {
+    Id(DocumentId),
+    Path(&'a Path)
+}
+
+impl From<DocumentId> for DocumentRef<'_> {
+    fn from(doc_id: DocumentId) -> Self {
+        DocumentRef::Id(doc_id)
+    }
+}
+
+impl<'a> From<&'a Path> for DocumentRef<'a> {
+    fn from(path: &'a Path) -> Self {
+        DocumentRef::Path(path)
+    }
+}
+
+impl<'a> From<&'a PathBuf> for DocumentRef<'a> {
+    fn from(path: &'a PathBuf) -> Self {
+        DocumentRef::Path(path)
+    }
+}
+
+/// The key a term of `field` is stored under in the segments, `field:term`.
+/// Field names have no colon, so the key splits back at the first one even
+/// when the term has colons of its own.
+pub fn field_term(field: &str, term: &str) -> String {
+    format!("{field}:{term}")
+}
+
+/// Splits a key made by `field_term` into its field and term. A key without
+/// a colon is taken as a term of the default field.
+pub fn split_field_term(key: &str) -> (&str, &str) {
+    key.split_once(':').unwrap_or((DEFAULT_FIELD, key))
+}
+
+#[test]
+pub fn test_field_term() {
+    assert_eq!(field_term(DEFAULT_FIELD, "title:rust"), "content:title:rust");
+    assert_eq!(split_field_term("content:title:rust"), (DEFAULT_FIELD, "title:rust"));
+    assert_eq!(split_field_term("title:rust"), ("title", "rust"));
+    assert_eq!(split_field_term("rust"), (DEFAULT_FIELD, "rust"));
+}
+
+#[test]
+pub fn test_content_hash() {
+    let document = Document::new("a").field("title", "Rust").field("content", "fast");
+    assert_eq!(document.content_hash(), Document::new("b").field("title", "Rust").field("content", "fast").content_hash());
+    assert_ne!(document.content_hash(), Document::new("a").field("title", "Rust").field("content", "fast!").content_hash());
+    assert_ne!(Document::new("a").field("ab", "c").content_hash(), Document::new("a").field("a", "bc").content_hash());
+}
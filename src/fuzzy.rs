@@ -0,0 +1,104 @@
+/// A Levenshtein automaton for one query term.
+///
+/// The automaton is fed a candidate one character at a time; each state is a
+/// row of the edit distance matrix, which is enough to tell both whether the
+/// characters seen so far are within `max_distance` of the query and whether
+/// any continuation still could be. With `transpositions` swapping two
+/// neighbouring characters counts as a single edit (Damerau-Levenshtein).
+#[derive(Debug, Clone)]
+pub struct LevenshteinAutomaton {
+    query: Vec<char>,
+    max_distance: usize,
+    transpositions: bool
+}
+
+#[derive(Debug, Clone)]
+pub struct State {
+    row: Vec<usize>,
+    // the row and character before this one, needed for transpositions
+    previous: Option<(Vec<usize>, char)>
+}
+
+impl LevenshteinAutomaton {
+    pub fn new(query: &str, max_distance: usize, transpositions: bool) -> Self {
+        Self { query: query.chars().collect(), max_distance, transpositions }
+    }
+
+    pub fn start(&self) -> State {
+        State { row: (0..=self.query.len()).collect(), previous: None }
+    }
+
+    pub fn step(&self, state: &State, c: char) -> State {
+        let mut row = Vec::with_capacity(state.row.len());
+        row.push(state.row[0] + 1);
+        for i in 1..=self.query.len() {
+            let cost = if self.query[i - 1] == c { 0 } else { 1 };
+            let mut distance = (state.row[i - 1] + cost).min(state.row[i] + 1).min(row[i - 1] + 1);
+            if self.transpositions && i > 1
+                && let Some((previous_row, previous_char)) = &state.previous
+                && self.query[i - 1] == *previous_char && self.query[i - 2] == c {
+                distance = distance.min(previous_row[i - 2] + 1);
+            }
+            row.push(distance);
+        }
+        State { row, previous: Some((state.row.clone(), c)) }
+    }
+
+    /// Edit distance between the query and everything fed into `state`.
+    pub fn distance(&self, state: &State) -> usize {
+        state.row[self.query.len()]
+    }
+
+    pub fn is_match(&self, state: &State) -> bool {
+        self.distance(state) <= self.max_distance
+    }
+
+    /// Whether any continuation of the input could still end in a match.
+    pub fn can_match(&self, state: &State) -> bool {
+        state.row.iter().min().is_some_and(|&min| min <= self.max_distance)
+    }
+
+    /// Runs the automaton over a sorted term dictionary and returns every term
+    /// within `max_distance`, with its distance. States are shared between
+    /// neighbouring terms for as long as they share a prefix, and once a
+    /// prefix can no longer match the terms continuing it are not walked.
+    pub fn matches<'a>(&self, sorted_terms: impl IntoIterator<Item = &'a str>) -> Vec<(&'a str, usize)> {
+        let mut results = Vec::new();
+        let mut previous: Vec<char> = Vec::new();
+        // states[i] is the state after the first i characters of `previous`
+        let mut states = vec![self.start()];
+        for term in sorted_terms {
+            let chars: Vec<char> = term.chars().collect();
+            let common = previous.iter().zip(chars.iter()).take_while(|(a, b)| a == b).count();
+            states.truncate(common + 1);
+            previous.truncate(common);
+            let mut alive = true;
+            for &c in &chars[states.len() - 1..] {
+                let last = states.last().unwrap();
+                if !self.can_match(last) {
+                    alive = false;
+                    break;
+                }
+                let next = self.step(last, c);
+                states.push(next);
+                previous.push(c);
+            }
+            let last = states.last().unwrap();
+            if alive && states.len() == chars.len() + 1 && self.is_match(last) {
+                results.push((term, self.distance(last)));
+            }
+        }
+        results
+    }
+}
+
+#[test]
+pub fn test_levenshtein_automaton() {
+    let automaton = LevenshteinAutomaton::new("rust", 1, true);
+    let terms = ["bust", "rst", "rus", "rust", "rusty", "rusts", "rutss", "srut", "trust"];
+    assert_eq!(automaton.matches(terms), vec![("bust", 1), ("rst", 1), ("rus", 1), ("rust", 0), ("rusty", 1), ("rusts", 1), ("trust", 1)]);
+
+    assert_eq!(LevenshteinAutomaton::new("rust", 1, true).matches(["rsut"]), vec![("rsut", 1)]);
+    assert!(LevenshteinAutomaton::new("rust", 1, false).matches(["rsut"]).is_empty());
+    assert_eq!(LevenshteinAutomaton::new("rust", 2, false).matches(["rsut"]), vec![("rsut", 2)]);
+}
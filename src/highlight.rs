@@ -0,0 +1,143 @@
+use std::collections::HashSet;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::analysis::Token;
+
+/// Cuts the passages of a document that best show why it matched and marks
+/// the query terms inside them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Highlighter {
+    pub pre_tag: String,
+    pub post_tag: String,
+    /// Upper bound on the length of a fragment, in characters.
+    pub fragment_len: usize,
+    pub max_fragments: usize
+}
+
+impl Default for Highlighter {
+    fn default() -> Self {
+        Self { pre_tag: "<em>".to_string(), post_tag: "</em>".to_string(), fragment_len: 100, max_fragments: 3 }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Word {
+    // byte range in the content
+    start: usize,
+    end: usize,
+    // char offsets, fragment lengths are measured in these
+    char_start: usize,
+    char_end: usize,
+    term: Option<String>
+}
+
+impl Highlighter {
+    pub fn new(pre_tag: &str, post_tag: &str, fragment_len: usize, max_fragments: usize) -> Self {
+        Self { pre_tag: pre_tag.to_string(), post_tag: post_tag.to_string(), fragment_len, max_fragments }
+    }
+
+    /// Returns up to `max_fragments` highlighted fragments of `content`, in
+    /// document order. `tokens` is `content` as analyzed for indexing; a word
+    /// is highlighted when a token overlapping it is one of `terms`.
+    pub fn fragments(&self, content: &str, tokens: &[Token], terms: &HashSet<String>) -> Vec<String> {
+        let char_offsets: Vec<usize> = content.char_indices().map(|(idx, _)| idx).collect();
+        let char_at = |byte: usize| char_offsets.partition_point(|&idx| idx < byte);
+        let mut matched: Vec<&Token> = tokens.iter().filter(|token| terms.contains(&token.text)).collect();
+        matched.sort_by_key(|token| token.start);
+        let words: Vec<Word> = content.unicode_word_indices()
+            .map(|(start, word)| {
+                let end = start + word.len();
+                let first = matched.partition_point(|token| token.end <= start);
+                let term = matched.get(first).filter(|token| token.start < end).map(|token| token.text.clone());
+                Word { start, end, char_start: char_at(start), char_end: char_at(end), term }
+            })
+            .collect();
+
+        let mut windows: Vec<(usize, usize)> = Vec::new();
+        while windows.len() < self.max_fragments {
+            let Some(window) = self.best_window(&words, &windows) else { break };
+            windows.push(window);
+        }
+        windows.sort_unstable();
+        windows.into_iter().map(|(first, last)| self.render(content, &words, first, last)).collect()
+    }
+
+    /// The best scoring run of words that starts at a match, fits in
+    /// `fragment_len` and does not overlap the windows already taken.
+    fn best_window(&self, words: &[Word], taken: &[(usize, usize)]) -> Option<(usize, usize)> {
+        let free = |idx: usize| taken.iter().all(|&(first, last)| idx < first || idx > last);
+        let mut best: Option<((usize, usize), (usize, usize))> = None;
+        for first in (0..words.len()).filter(|&idx| words[idx].term.is_some() && free(idx)) {
+            let mut last = first;
+            while last + 1 < words.len() && free(last + 1)
+                && words[last + 1].char_end - words[first].char_start <= self.fragment_len {
+                last += 1;
+            }
+            let matched: Vec<&String> = words[first..=last].iter().filter_map(|word| word.term.as_ref()).collect();
+            let distinct: HashSet<&String> = matched.iter().copied().collect();
+            // distinct terms count most, repeated ones break ties
+            let score = (distinct.len(), matched.len());
+            if best.is_none_or(|(best_score, _)| score > best_score) {
+                best = Some((score, (first, last)));
+            }
+        }
+        let (mut first, mut last) = best?.1;
+        let span = |first: usize, last: usize| words[last].char_end - words[first].char_start;
+        // trim to the last match, then spend half the spare room on leading context
+        while last > first && words[last].term.is_none() {
+            last -= 1;
+        }
+        let lead = self.fragment_len.saturating_sub(span(first, last)) / 2;
+        let anchor = words[first].char_start;
+        while first > 0 && free(first - 1) && anchor - words[first - 1].char_start <= lead {
+            first -= 1;
+        }
+        while last + 1 < words.len() && free(last + 1) && span(first, last + 1) <= self.fragment_len {
+            last += 1;
+        }
+        Some((first, last))
+    }
+
+    fn render(&self, content: &str, words: &[Word], first: usize, last: usize) -> String {
+        let mut fragment = String::new();
+        if first > 0 {
+            fragment.push('…');
+        }
+        let mut cursor = words[first].start;
+        for word in &words[first..=last] {
+            fragment.push_str(&content[cursor..word.start]);
+            if word.term.is_some() {
+                fragment.push_str(&self.pre_tag);
+                fragment.push_str(&content[word.start..word.end]);
+                fragment.push_str(&self.post_tag);
+            } else {
+                fragment.push_str(&content[word.start..word.end]);
+            }
+            cursor = word.end;
+        }
+        if last + 1 < words.len() {
+            fragment.push('…');
+        }
+        fragment
+    }
+}
+
+#[test]
+pub fn test_fragments() {
+    use super::analysis::{Analyzer, TextAnalyzer};
+
+    let content = "Java is popular. Many teams pick Rust today because Rust is memory safe and fast, and systems code in Rust is pleasant.";
+    let tokens = TextAnalyzer::default().analyze(content);
+    let terms: HashSet<String> = ["rust", "safe"].iter().map(|term| term.to_string()).collect();
+    let highlighter = Highlighter::new("[", "]", 40, 1);
+    let fragments = highlighter.fragments(content, &tokens, &terms);
+    assert_eq!(fragments.len(), 1);
+    assert!(fragments[0].contains("[Rust] is memory [safe]"), "{fragments:?}");
+    assert!(fragments[0].chars().count() <= 40 + 2 + 4 * 2);
+
+    let fragments = Highlighter::new("[", "]", 20, 3).fragments(content, &tokens, &terms);
+    assert_eq!(fragments.len(), 3);
+    assert!(fragments.iter().all(|fragment| fragment.contains('[')));
+    assert!(Highlighter::default().fragments(content, &tokens, &HashSet::new()).is_empty());
+}
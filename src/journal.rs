@@ -1,8 +1,16 @@
-use std::{fs::{File, OpenOptions}, io::{self, BufRead, Error, ErrorKind, Read, Write}, path::Path};
+use std::{borrow::Cow, fs::File, io::{self, BufRead, Error, Write}, path::{Path, PathBuf}};
 
 use serde::{Deserialize, Serialize};
 
+use super::atomic_write;
 
+
+/// Size a log file may grow to before the journal moves on to the next one.
+pub const LOG_FILE_SIZE: i32 = 1 << 20;
+
+/// An append-only journal of line records, spread over `wal{n}.log` files in
+/// `dir_path`. The files are found by listing the directory, so moving on to
+/// a new one never rewrites the snapshot.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct WAL {
     #[serde(skip)] 
@@ -11,124 +19,157 @@ pub struct WAL {
     index: i32,
     file_path: String,
     dir_path: String,
-    history : Vec<String>                         
+    // snapshots written before the journal could live elsewhere are in the working directory
+    #[serde(default = "default_snapshot_path")]
+    snapshot_path: String
+}
+
+fn default_snapshot_path() -> String { 
+    "./wal.bin".to_string()
 }
 
 impl WAL { 
 
-    pub fn load_from_disk() -> io::Result<Self>{ 
-        let file_path = "./wal.bin";
+    pub fn load_from_disk(file_path: &Path) -> io::Result<Self>{ 
         if std::fs::exists(file_path)? && !std::fs::metadata(file_path)?.is_dir() { 
-            let  mut file = File::open(file_path)?;
+            let  file = File::open(file_path)?;
             let reader = io::BufReader::new(&file);
             let wal: Self = serde_json::from_reader(reader)?;
             return Ok(wal)
         }
-        return Err(Error::new(io::ErrorKind::NotFound, "file npt found"))
+        Err(Error::new(io::ErrorKind::NotFound, "file npt found"))
     }
     pub fn new(size: i32, index: i32) -> io::Result<Self> { 
-        if let Ok(wal) = Self::load_from_disk() { 
-            println!("wal loaded from disk : {wal:?}");
-            return Ok(wal);
-        }
-        let file_path = format!("./logger/wal{}.log", index);
-        let dir_path = "./logger".to_string();
-        
-        match Self::create_file(dir_path.clone(), file_path.clone()){
-            Ok(file) => { 
-                let mut wal = Self{
-                    file: Some(file),
+        Self::open(Path::new("."), size, index)
+    }
+
+    /// Opens the journal kept under `dir`: the log files in `dir/logger` and
+    /// the snapshot at `dir/wal.bin`. A file grows to `size` bytes before the
+    /// next one is started.
+    pub fn open(dir: &Path, size: i32, index: i32) -> io::Result<Self> { 
+        let snapshot_path = dir.join("wal.bin");
+        let mut wal = match Self::load_from_disk(&snapshot_path) { 
+            Ok(wal) => wal,
+            Err(_) => { 
+                let dir_path = dir.join("logger").to_string_lossy().into_owned();
+                let file_path = format!("{dir_path}/wal{index}.log");
+                let wal = Self { 
+                    file: None,
                     size,
-                    index: index,
-                    file_path: file_path.clone(),
-                    dir_path ,
-                    history: Vec::new()     
+                    index: index + 1,
+                    file_path,
+                    dir_path,
+                    snapshot_path: snapshot_path.to_string_lossy().into_owned()
                 };
-                wal.history.push(file_path.clone());
-                wal.index += 1;
-                Ok(wal)
-            } 
-            Err(err) => Err(err),
+                std::fs::create_dir_all(&wal.dir_path)?;
+                wal.flash_snapshot_to_disk()?;
+                wal
+            }
+        };
+        wal.size = size;
+        // appending goes on in the last file there is
+        if let Some((index, file_path)) = wal.log_files()?.pop() { 
+            wal.index = index + 1;
+            wal.file_path = file_path;
         }
-        
+        wal.file = Some(Self::create_file(wal.dir_path.clone(), wal.file_path.clone())?);
+        Ok(wal)
     }
 
     pub fn create_file(dir_path:String, file_path: String) -> io::Result<File>{ 
-        let dir_exists = match std::fs::exists(dir_path.clone()) { 
-            Ok(val) => val,
-            Err(_) => false
-        };
+        let dir_exists = std::fs::exists(dir_path.clone()).unwrap_or_default();
         if !dir_exists { 
-            if let Err(err) = std::fs::create_dir_all(dir_path) { 
-                return Err(err);
-            }
+            std::fs::create_dir_all(&dir_path)?;
         }
-        match std::fs::OpenOptions::new().create(true).write(true).append(true).open(file_path.clone()) {
-            Ok(file) => Ok(file)  ,
-            Err(err) => Err(err),
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(file_path)?;
+        // the entry of a new file has to reach the disk too
+        File::open(&dir_path)?.sync_all()?;
+        Ok(file)
+    }
+
+    /// The log files in `dir_path` with their numbers, oldest first.
+    fn log_files(&self) -> io::Result<Vec<(i32, String)>> { 
+        let mut files = Vec::new();
+        if !std::fs::exists(&self.dir_path)? { 
+            return Ok(files)
+        }
+        for entry in std::fs::read_dir(&self.dir_path)? { 
+            let name = entry?.file_name();
+            let number = name.to_str()
+                .and_then(|name| name.strip_prefix("wal")?.strip_suffix(".log")?.parse().ok());
+            if let Some(number) = number { 
+                files.push((number, format!("{}/wal{number}.log", self.dir_path)));
+            }
         }
+        files.sort_unstable();
+        Ok(files)
     }
 
     pub fn flash_snapshot_to_disk(&self) -> io::Result<()> { 
-        let file_path = "./wal.bin";
-        println!("pushing snapshots");
-        
-        let mut file = OpenOptions::new().read(true).write(true).create(true).open(file_path)?;
-        let writer = io::BufWriter::new(&file);
-        let _ = serde_json::to_writer(writer, self)?;
-        let _ = file.flush()?;
-        Ok(())
+        atomic_write(Path::new(&self.snapshot_path), &serde_json::to_vec(self)?)
     }
 
-
+    /// Appends a record, as one line, and syncs it to disk before returning.
+    /// A full log file is left for a new one first.
     pub fn log(&mut self, mut record: String) -> io::Result<()>{ 
-        if self.file.is_none() { 
-            if let Ok(file) = Self::create_file(self.dir_path.clone(), self.file_path.clone()) { 
-                self.file = Some(file);   
-                    
-                let _ = self.flash_snapshot_to_disk();
-            }
+        if !record.ends_with('\n') { 
+            record.push('\n');
         }
-        let size = std::fs::metadata(self.file_path.clone())?.len();
-        println!("file size :{}", size as usize);
-        if size as usize + record.as_bytes().len() >= 100 { 
-            let filepath = format!("./logger/wal{}.log", self.index);
+        let size = match std::fs::metadata(&self.file_path) { 
+            Ok(metadata) => metadata.len() as usize,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => 0,
+            Err(err) => return Err(err)
+        };
+        if size > 0 && size + record.len() > self.size as usize { 
+            self.file_path = format!("{}/wal{}.log", self.dir_path, self.index);
             self.index += 1;
-            let mut file  = Self::create_file(self.dir_path.clone(), filepath.clone())?;
-            self.history.push(filepath.clone());
-            self.file_path = filepath;
-            self.file = Some(file);
-            let _ = self.flash_snapshot_to_disk();         
+            self.file = None;
         }
-        
-        let mut file = self.file.as_ref().unwrap();    
-        
-        if !record.ends_with('\n') { 
-            record.push('\n');
+        let file = match &mut self.file { 
+            Some(file) => file,
+            None => self.file.insert(Self::create_file(self.dir_path.clone(), self.file_path.clone())?)
+        };
+        file.write_all(record.as_bytes())?;
+        file.sync_data()
+    }
+
+    /// Deletes every journal file along with the snapshot, so the next
+    /// `WAL::open` starts an empty journal.
+    pub fn destroy(&self) -> io::Result<()> { 
+        for (_, filepath) in self.log_files()? { 
+            std::fs::remove_file(filepath)?;
+        }
+        if std::fs::exists(&self.snapshot_path)? { 
+            std::fs::remove_file(&self.snapshot_path)?;
         }
-        let _ = file.write_all(record.as_bytes());
-        let _ = file.flush();
         Ok(())
     }
+
+    /// Empties the journal, once everything in it is kept elsewhere: the log
+    /// files and the snapshot are deleted and a fresh first file started.
+    pub fn reset(&mut self) -> io::Result<()> { 
+        self.destroy()?;
+        self.file_path = format!("{}/wal0.log", self.dir_path);
+        self.index = 1;
+        self.file = Some(Self::create_file(self.dir_path.clone(), self.file_path.clone())?);
+        Ok(())
+    }
+
     pub fn find_last_page_last_written_offset(&mut self)  -> (usize, usize){ 
         if self.read_records().is_empty() { 
             return (0, 0)
         }
-        match self.read_records().iter().map(|line| { 
-            let splits: Vec<&str> = line.split(",").collect();
-            let offset : usize = splits[1].parse().expect("expected a usize");
-            let size : usize = splits[2].parse().expect("expected a usize");
-            (offset, size)
-        }).max_by_key(|(offset,usize)| *offset) {
+        match self.read_records().iter().filter_map(|line| parse_record(line))
+            .map(|(_, offset, size)| (offset, size))
+            .max_by_key(|(offset,_usize)| *offset) {
             Some((offset, size)) => (offset, size),
             None => (0, 0),
         }
     }
     pub fn read_records(&mut self) -> Vec<String> {
-        println!("file_history :{:?}", self.history);
-        let records : Vec<String> = self.history.iter().
-            map(|filepath| File::open(filepath.clone())).
-            filter(|file| file.is_ok()).map(Result::unwrap).
+        let files = self.log_files().unwrap_or_default();
+        let records : Vec<String> = files.into_iter().
+            flat_map(|(_, filepath)| File::open(filepath)).
             flat_map(|file| { 
                 let mut records = Vec::new();
                 let mut reader = io::BufReader::new(file);
@@ -144,21 +185,134 @@ impl WAL {
     }
 }
 
+/// Splits a `term,offset,size` record. The numbers are taken from the end, so
+/// terms containing commas survive; malformed lines give `None`.
+pub fn parse_record(line: &str) -> Option<(Cow<'_, str>, usize, usize)> { 
+    let mut splits = line.rsplitn(3, ',');
+    let size = splits.next()?.parse().ok()?;
+    let offset = splits.next()?.parse().ok()?;
+    Some((unescape_term(splits.next()?), offset, size))
+}
+
+/// Escapes the line breaks and backslashes of a term, so its record stays on
+/// one line.
+pub fn escape_term(term: &str) -> Cow<'_, str> { 
+    if !term.contains(['\\', '\n', '\r']) { 
+        return Cow::Borrowed(term)
+    }
+    Cow::Owned(term.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r"))
+}
+
+/// Reverses `escape_term`.
+fn unescape_term(term: &str) -> Cow<'_, str> { 
+    if !term.contains('\\') { 
+        return Cow::Borrowed(term)
+    }
+    let mut unescaped = String::with_capacity(term.len());
+    let mut chars = term.chars();
+    while let Some(c) = chars.next() { 
+        if c != '\\' { 
+            unescaped.push(c);
+            continue
+        }
+        match chars.next() { 
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\')
+        }
+    }
+    Cow::Owned(unescaped)
+}
+
+/// Marks a deleted document in the journal, logged as `#tombstone,{id}`.
+pub const TOMBSTONE: &str = "#tombstone";
+
+/// The document id of a tombstone record, `None` for any other line.
+pub fn parse_tombstone(line: &str) -> Option<usize> { 
+    line.strip_prefix(TOMBSTONE)?.strip_prefix(',')?.parse().ok()
+}
+
+/// Marks a document added to the document table, logged as `#doc {json}`.
+pub const DOCUMENT: &str = "#doc ";
+
+/// A document as the document table keeps it, journaled when it is added so
+/// the table can be caught up past its last snapshot. Its stored fields go
+/// along, as the document store only writes them a block at a time.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct DocumentRecord { 
+    pub doc_id: usize,
+    pub path: PathBuf,
+    pub hash: u64,
+    /// Token count of every indexed field.
+    pub lengths: Vec<(String, usize)>,
+    /// The fields to store, by name.
+    #[serde(default)]
+    pub fields: Vec<(String, String)>,
+    /// Keys of the terms the document was indexed under.
+    #[serde(default)]
+    pub terms: Vec<String>
+}
+
+impl DocumentRecord { 
+    pub fn to_record(&self) -> io::Result<String> { 
+        Ok(format!("{DOCUMENT}{}", serde_json::to_string(self)?))
+    }
+}
+
+/// The document of a `#doc` record, `None` for any other line.
+pub fn parse_document(line: &str) -> Option<DocumentRecord> { 
+    serde_json::from_str(line.strip_prefix(DOCUMENT)?).ok()
+}
 
 #[test]
 pub fn test_wal() { 
-    let mut wal = WAL::new(4096, 0).unwrap();
-    let _ = wal.log("this is first record".to_string());
-    let _ = wal.log("this is second record".to_string());
-    let _ = wal.log("this is third record".to_string());
-    let _ = wal.log("this is fourth record".to_string());
-
-    let _ = wal.log("this is fifth record".to_string());
-    let _ = wal.log("this is sixth record".to_string());
-    let _ = wal.log("this is seventh record".to_string());
-    let _ = wal.log("this is eighth record".to_string());
-    
-    let result = wal.read_records();
-    println!("result: {result:?}");    
+    let dir = super::temp_index_dir("wal");
+    let mut wal = WAL::open(&dir, 64, 0).unwrap();
+    let records: Vec<String> = (0..8).map(|i| format!("this is record {i}")).collect();
+    for record in &records { 
+        wal.log(record.clone()).unwrap();
+    }
+    // a file holds three records before the next one is started
+    assert_eq!(wal.log_files().unwrap().len(), 3);
+    assert_eq!(wal.read_records(), records);
+    drop(wal);
+
+    let mut wal = WAL::open(&dir, LOG_FILE_SIZE, 0).unwrap();
+    wal.log("this is record 8".to_string()).unwrap();
+    assert_eq!(wal.log_files().unwrap().len(), 3);
+    assert_eq!(wal.read_records().len(), 9);
+    wal.reset().unwrap();
+    assert!(wal.read_records().is_empty());
+    let _ = std::fs::remove_dir_all(&dir);
 }
 
+
+#[test]
+pub fn test_parse_record() { 
+    assert_eq!(parse_record("rust,10,4"), Some(("rust".into(), 10, 4)));
+    assert_eq!(parse_record("1,000,36,4"), Some(("1,000".into(), 36, 4)));
+    // a term broken over lines is escaped onto one
+    let term = "a\\n\n#tombstone,0\r";
+    let line = format!("{},36,4", escape_term(term));
+    assert_eq!(line.lines().count(), 1);
+    assert_eq!(parse_record(&line), Some((term.into(), 36, 4)));
+    assert_eq!(parse_record("rust,10"), None);
+    assert_eq!(parse_record("rust,ten,4"), None);
+    // tombstones never pass for postings
+    assert_eq!(parse_record("#tombstone,7"), None);
+    assert_eq!(parse_tombstone("#tombstone,7"), Some(7));
+    assert_eq!(parse_tombstone("#tombstone,10,4"), None);
+    let document = DocumentRecord { 
+        doc_id: 3,
+        path: PathBuf::from("./docs/a,1,2"),
+        hash: 42,
+        lengths: vec![("content".to_string(), 6)],
+        fields: vec![("content".to_string(), "two\nlines".to_string())],
+        terms: vec!["content:two".to_string(), "content:line".to_string()]
+    };
+    let line = document.to_record().unwrap();
+    assert_eq!(parse_record(&line), None);
+    assert!(!line.contains('\n'));
+    assert_eq!(parse_document(&line), Some(document));
+}
@@ -0,0 +1,1521 @@
+pub mod analysis;
+pub mod collector;
+pub mod dictionary;
+pub mod document;
+pub mod fuzzy;
+pub mod highlight;
+pub mod page;
+pub mod parser;
+pub mod storage;
+pub mod store;
+pub mod journal;
+pub mod posting;
+pub mod query;
+pub mod schema;
+pub mod scoring;
+pub mod stemmer;
+pub mod stopwords;
+pub mod synonyms;
+
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::{collections::{HashMap, HashSet}, io, path::PathBuf};
+use bincode::{Encode, Decode};
+
+use analysis::{Analyzer, AnalyzerRegistry, TextAnalyzer};
+use collector::{Cursor, Hit, SearchPage, TopDocs};
+use dictionary::TermDictionary;
+use journal::{DocumentRecord, WAL};
+use document::{Document, DocumentRef, Update, field_term, split_field_term};
+use fuzzy::LevenshteinAutomaton;
+use highlight::Highlighter;
+use posting::Posting;
+use query::{DocSet, Query};
+use schema::{FieldType, Schema, SchemaError};
+use scoring::{Bm25, Similarity, TermStats};
+use std::sync::Arc;
+use storage::SegmentStore;
+use store::{DocStore, StoredDocument};
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+pub type DocumentId = usize;
+
+// relative to the index directory
+const SEGMENTS_PATH: &str = "segments/index.seg";
+const DICTIONARY_PATH: &str = "segments/terms.dict";
+const DOCS_PATH: &str = "docs.bin";
+const SCHEMA_PATH: &str = "schema.bin";
+const STORE_PATH: &str = "store/docs.store";
+const JOURNAL_PATH: &str = "journal";
+
+/// Starts the document table snapshot, followed by the format version as a
+/// `u32`. Snapshots without it hold a `LegacyDocs`. Up to version 1 the terms
+/// of the default field were stored under bare keys, see `qualify_keys`.
+const DOCS_MAGIC: &[u8; 4] = b"RDOC";
+const DOCS_VERSION: u32 = 2;
+
+/// The field plain `add_document` content is indexed under.
+pub const DEFAULT_FIELD: &str = "content";
+
+/// Writes `bytes` to a temporary file next to `path` and renames it over
+/// `path`, so a crash leaves either the old or the new content behind.
+pub fn atomic_write(path: &Path, bytes: &[u8]) -> io::Result<()> { 
+    let tmp_path = path.with_extension("tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    replace_file(&tmp_path, path)
+}
+
+/// Renames `from` over `to` and syncs their directory, without which the
+/// rename itself may not survive a crash.
+pub fn replace_file(from: &Path, to: &Path) -> io::Result<()> { 
+    std::fs::rename(from, to)?;
+    let dir = to.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    File::open(dir)?.sync_all()
+}
+
+#[derive(Serialize, Deserialize, Encode, Decode, Clone, Debug, Default)]
+pub struct WritableDocs { 
+    docs : HashMap<DocumentId, PathBuf>,
+    current_doc_id : usize,
+    // per field, for length normalisation
+    lengths : HashMap<String, FieldLengths>,
+    // id and content hash of the document at every path, see `Document::content_hash`
+    paths : HashMap<PathBuf, (DocumentId, u64)>,
+    // deleted since the last compaction, their postings are still in the segments
+    deleted : HashSet<DocumentId>
+}
+
+/// The document table as it was snapshotted before it had a version.
+#[derive(Decode)]
+struct LegacyDocs { 
+    docs : HashMap<DocumentId, PathBuf>,
+    current_doc_id : usize
+}
+
+impl From<LegacyDocs> for WritableDocs { 
+    fn from(legacy: LegacyDocs) -> Self { 
+        // without a content hash the next update of every path replaces it,
+        // field lengths come back as documents are indexed again
+        let paths = legacy.docs.iter().map(|(doc_id, path)| (path.clone(), (*doc_id, 0))).collect();
+        Self { docs: legacy.docs, current_doc_id: legacy.current_doc_id, paths, ..Default::default() }
+    }
+}
+
+impl WritableDocs { 
+    fn insert(&mut self, document: DocumentRecord) { 
+        self.paths.insert(document.path.clone(), (document.doc_id, document.hash));
+        self.docs.insert(document.doc_id, document.path);
+        for (field, length) in document.lengths { 
+            let field_lengths = self.lengths.entry(field).or_default();
+            field_lengths.docs.insert(document.doc_id, length);
+            field_lengths.total += length;
+        }
+        self.current_doc_id = self.current_doc_id.max(document.doc_id + 1);
+    }
+
+    /// Drops a document from the table and the field lengths, keeping its id
+    /// until the next compaction. `false` when there is no such document.
+    fn delete(&mut self, doc_id: DocumentId) -> bool { 
+        let Some(path) = self.docs.remove(&doc_id) else { 
+            return false
+        };
+        // the path may lead to a newer version already
+        if self.id_of(&path) == Some(doc_id) { 
+            self.paths.remove(&path);
+        }
+        for lengths in self.lengths.values_mut() { 
+            if let Some(length) = lengths.docs.remove(&doc_id) { 
+                lengths.total -= length;
+            }
+        }
+        self.deleted.insert(doc_id)
+    }
+
+    /// The id of the document indexed from `path`.
+    pub fn id_of(&self, path: &Path) -> Option<DocumentId> { 
+        self.paths.get(path).map(|(doc_id, _)| *doc_id)
+    }
+}
+
+/// Token count of a field in every document that has it, and their sum.
+#[derive(Serialize, Deserialize, Encode, Decode, Clone, Debug, Default)]
+struct FieldLengths { 
+    docs : HashMap<DocumentId, usize>,
+    total : usize
+}
+
+/// Scores documents one at a time against the positive terms of a query.
+struct Scorer<'a> { 
+    terms : Vec<ScoredTerm<'a>>,
+    docs_count : usize,
+    similarity : &'a dyn Similarity
+}
+
+struct ScoredTerm<'a> { 
+    postings : Vec<Posting>,
+    // including the boost of the field
+    weight : f32,
+    lengths : Option<&'a FieldLengths>,
+    avg_doc_len : f32
+}
+
+impl<'a> Scorer<'a> { 
+    /// Takes the postings of every term with its weight and field, see
+    /// `InvertedIndex::scoring_postings`.
+    fn new(docs: &'a WritableDocs, terms: Vec<(Vec<Posting>, f32, String)>, similarity: &'a dyn Similarity) -> Self { 
+        let terms = terms.into_iter().map(|(postings, weight, field)| { 
+            let lengths = docs.lengths.get(&field);
+            let avg_doc_len = lengths.filter(|lengths| !lengths.docs.is_empty())
+                .map_or(0.0, |lengths| lengths.total as f32 / lengths.docs.len() as f32);
+            ScoredTerm { postings, weight, lengths, avg_doc_len }
+        }).collect();
+        Self { terms, docs_count: docs.docs.len(), similarity }
+    }
+
+    /// Sums the score of every query term `doc` contains.
+    fn score(&self, doc: DocumentId) -> f32 { 
+        let mut score = 0.0;
+        for term in &self.terms { 
+            if let Ok(idx) = term.postings.binary_search_by_key(&doc, |posting| posting.doc_id) { 
+                let stats = TermStats { 
+                    // postings from before positions were kept still count one
+                    term_freq: term.postings[idx].positions.len().max(1),
+                    doc_len: term.lengths.and_then(|lengths| lengths.docs.get(&doc)).copied().unwrap_or(0),
+                    avg_doc_len: term.avg_doc_len,
+                    doc_freq: term.postings.len(),
+                    docs_count: self.docs_count
+                };
+                score += term.weight * self.similarity.score(&stats);
+            }
+        }
+        score
+    }
+}
+
+#[derive(Debug)]
+pub struct InvertedIndex { 
+    pub index : HashMap<String, Vec<Posting>>,
+    pub docs : WritableDocs,
+    pub weights : HashMap<String, usize>,
+    pub last_used : i32,
+    pub docs_count: usize,
+    pub cap: usize,
+    pub segment_store : SegmentStore,
+    pub store : DocStore,
+    pub dictionary : TermDictionary,
+    pub similarity : Arc<dyn Similarity>,
+    pub analyzer : Arc<dyn Analyzer>,
+    pub field_analyzers : HashMap<String, Arc<dyn Analyzer>>,
+    /// Analyzers fields are searched with, when not the one they are indexed with.
+    pub query_analyzers : HashMap<String, Arc<dyn Analyzer>>,
+    pub field_boosts : HashMap<String, f32>,
+    pub analyzers : AnalyzerRegistry,
+    /// Without a schema any field is accepted and indexed as text.
+    pub schema : Option<Schema>,
+    // documents added and deleted since the document table was last snapshotted
+    journal : WAL,
+    dir : PathBuf
+}
+
+impl Default for InvertedIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InvertedIndex { 
+    /// Opens the index in the working directory.
+    pub fn new() -> Self { 
+        Self::open(Path::new("."), None).expect("unable to open the index")
+    }
+
+    /// Opens the index in the working directory with `schema`, see `open`.
+    pub fn with_schema(schema: Schema) -> io::Result<Self> { 
+        Self::open(Path::new("."), Some(schema))
+    }
+
+    /// Opens the index kept in `dir`, creating it when there is none. A
+    /// `schema` has to be compatible with the schema the index was created
+    /// with, and is persisted.
+    pub fn open(dir: &Path, schema: Option<Schema>) -> io::Result<Self> { 
+        std::fs::create_dir_all(dir.join(SEGMENTS_PATH).parent().unwrap())?;
+        let segment_store = SegmentStore::new(&dir.join(SEGMENTS_PATH), dir, 4096, 16)?;
+        let mut journal = WAL::open(&dir.join(JOURNAL_PATH), journal::LOG_FILE_SIZE, 0)?;
+        let (mut docs, version) = Self::load_docs_from_disk(dir)?;
+        let mut store = DocStore::open(&dir.join(STORE_PATH))?;
+        // the snapshot is caught up with the journal, which may still hold
+        // records from before it when a checkpoint was cut short
+        let snapshot_next_id = docs.current_doc_id;
+        for record in journal.read_records() { 
+            if let Some(mut document) = journal::parse_document(&record) { 
+                if document.doc_id < snapshot_next_id { 
+                    continue
+                }
+                // stored fields still queued for a block when the index was closed
+                if !store.contains(document.doc_id) { 
+                    let fields = std::mem::take(&mut document.fields);
+                    let terms = std::mem::take(&mut document.terms);
+                    store.add(document.doc_id, StoredDocument { document: Document { path: document.path.clone(), fields }, terms })?;
+                }
+                docs.insert(document);
+            } else if let Some(doc_id) = journal::parse_tombstone(&record) { 
+                docs.delete(doc_id);
+            }
+        }
+        let schema = Self::load_schema(&dir.join(SCHEMA_PATH), schema, &docs)?;
+        let dictionary = Self::load_dictionary(&dir.join(DICTIONARY_PATH), &segment_store, &docs);
+        let stale = dictionary.is_none();
+        let mut inverted_index = Self { 
+            index : HashMap::new(),
+            docs: docs.clone(),
+            weights: HashMap::new(),
+            docs_count: docs.docs.len(),
+            last_used: 0,
+            cap: 5,
+            segment_store,
+            store,
+            dictionary: dictionary.unwrap_or_default(),
+            similarity: Arc::new(Bm25::default()),
+            analyzer: Arc::new(TextAnalyzer::default()),
+            field_analyzers: HashMap::new(),
+            query_analyzers: HashMap::new(),
+            field_boosts: HashMap::new(),
+            analyzers: AnalyzerRegistry::default(),
+            schema,
+            journal,
+            dir: dir.to_path_buf()
+        };
+        if version < DOCS_VERSION { 
+            inverted_index.qualify_keys()?;
+        } else if stale { 
+            inverted_index.dictionary = inverted_index.rebuild_dictionary()?;
+        }
+        // an update can have indexed the new version but not got to delete
+        // the old one, which the path no longer leads to
+        let replaced: Vec<DocumentId> = inverted_index.docs.docs.iter()
+            .filter(|(doc_id, path)| inverted_index.docs.id_of(path) != Some(**doc_id))
+            .map(|(doc_id, _)| *doc_id)
+            .collect();
+        for doc_id in replaced { 
+            inverted_index.delete_document(doc_id)?;
+        }
+        println!("inverted index : {inverted_index:?}");
+        Ok(inverted_index)
+    }
+
+    /// Settles on the schema to open the index with: `schema` once it is
+    /// checked against the persisted one, or the persisted one. Either way
+    /// every field documents were indexed into must still be indexed.
+    fn load_schema(path: &Path, schema: Option<Schema>, docs: &WritableDocs) -> io::Result<Option<Schema>> { 
+        let existing = if std::fs::exists(path)? { Some(Schema::load(path)?) } else { None };
+        let schema = match (schema, existing) { 
+            (Some(schema), existing) => { 
+                if let Some(existing) = existing { 
+                    schema.check_compatible(&existing)?;
+                }
+                schema.save(path)?;
+                Some(schema)
+            },
+            (None, existing) => existing
+        };
+        if let Some(schema) = &schema { 
+            for field in docs.lengths.keys() { 
+                if !schema.get(field).is_some_and(|entry| entry.indexed) { 
+                    let reason = "documents were indexed into it but the schema does not index it".to_string();
+                    return Err(SchemaError::Incompatible { field: field.clone(), reason }.into())
+                }
+            }
+        }
+        Ok(schema)
+    }
+    
+        
+    
+    /// Loads the last snapshot of the document table, which may be behind
+    /// the document journal, along with its version. Snapshots from before
+    /// the table had a version are migrated and, like a missing snapshot,
+    /// given as version 0.
+    pub fn load_docs_from_disk(dir: &Path) -> io::Result<(WritableDocs, u32)> { 
+        let path = dir.join(DOCS_PATH);
+        if !std::fs::exists(&path)? {
+            return Ok((WritableDocs::default(), 0));
+        } 
+        let mut buf = Vec::new();
+        File::open(path)?.read_to_end(&mut buf)?;
+        let config = bincode::config::standard();
+        let invalid = |err| Error::new(ErrorKind::InvalidData, format!("can not decode document table: {err}"));
+        let Some(body) = buf.strip_prefix(DOCS_MAGIC) else { 
+            return bincode::decode_from_slice::<LegacyDocs, _>(&buf, config)
+                .map(|(legacy, _)| (legacy.into(), 0))
+                .map_err(invalid)
+        };
+        let version = body.first_chunk::<4>().map(|version| u32::from_le_bytes(*version));
+        let Some(version @ 1..=DOCS_VERSION) = version else { 
+            return Err(Error::new(ErrorKind::InvalidData, format!("unsupported document table version {version:?}")))
+        };
+        bincode::decode_from_slice(&body[4..], config)
+            .map(|(docs, _)| (docs, version))
+            .map_err(invalid)
+    }
+
+    /// Moves the terms of the default field from the bare keys they used to
+    /// be stored under to `content:term`, in the segments and in the terms
+    /// the document store keeps, and snapshots the document table at the
+    /// current version once done. A new index goes through here too, with
+    /// nothing to move.
+    fn qualify_keys(&mut self) -> io::Result<()> { 
+        let fields: HashSet<String> = self.docs.lengths.keys().cloned().collect();
+        // keys of the default field are left alone, so a migration cut
+        // short can run again
+        let qualify = |key: &str| match key.split_once(':') { 
+            Some((field, _)) if field == DEFAULT_FIELD || fields.contains(field) => key.to_string(),
+            _ => field_term(DEFAULT_FIELD, key)
+        };
+        let mut terms: Vec<String> = self.segment_store.terms().cloned().collect();
+        terms.sort_unstable();
+        let mut chunks = Vec::new();
+        for term in terms { 
+            let key = qualify(&term);
+            chunks.extend(self.segment_store.read_bytes(term)?.into_iter().map(|chunk| (key.clone(), chunk)));
+        }
+        self.segment_store.rewrite(&chunks)?;
+        self.store.rewrite(&self.dir.join(STORE_PATH), |_, mut document| { 
+            document.terms = document.terms.iter().map(|term| qualify(term)).collect();
+            Some(document)
+        })?;
+        self.index.clear();
+        self.dictionary = self.rebuild_dictionary()?;
+        self.sync()
+    }
+
+    /// Loads the term dictionary, `None` when it is missing or was not saved
+    /// after the last documents were added.
+    fn load_dictionary(path: &Path, segment_store: &SegmentStore, docs: &WritableDocs) -> Option<TermDictionary> { 
+        TermDictionary::load(path).ok()
+            .filter(|dictionary| dictionary.entries() == segment_store.entries())
+            .filter(|dictionary| dictionary.removed() == docs.deleted.len())
+    }
+
+    /// Builds the term dictionary from the postings in the segments, counting
+    /// the distinct documents of every term.
+    fn rebuild_dictionary(&mut self) -> io::Result<TermDictionary> { 
+        let mut dictionary = TermDictionary::default();
+        let terms: Vec<String> = self.segment_store.terms().cloned().collect();
+        for term in terms { 
+            let doc_freq = self.read_postings(&term)?.len();
+            dictionary.insert(&term, doc_freq, self.segment_store.chunks(&term));
+        }
+        // the postings read leave the deleted documents out
+        dictionary.set_removed(self.docs.deleted.len());
+        Ok(dictionary)
+    }
+
+    /// Persists the term dictionary next to the segments and writes out the
+    /// documents still waiting for a full block in the document store.
+    pub fn sync(&mut self) -> io::Result<()> { 
+        self.segment_store.sync()?;
+        self.store.flush()?;
+        self.checkpoint()?;
+        self.dictionary.save(&self.dir.join(DICTIONARY_PATH))
+    }
+
+    /// Snapshots the document table and empties the journal, which the
+    /// snapshot then covers.
+    fn checkpoint(&mut self) -> io::Result<()> { 
+        self.write_docs_to_disk()?;
+        self.journal.reset()
+    }
+
+    pub fn evict(&mut self) { 
+        println!("evicting");
+        if let Some((term, _)) = self.weights.iter().min_by_key(|(_, used)| *used)
+        .map(|(term,used)| (term.to_string(), *used)) { 
+            self.weights.remove(&term);
+            println!("evicting {term:?}");
+            self.index.remove(&term);
+        }
+        println!("after evicting the index is {:?}", self.index)
+    }
+
+    /// Snapshots the document table to a temporary file renamed over the
+    /// previous snapshot. Changes since the last snapshot are recovered from
+    /// the journal, so this only runs on `sync` and `compact`.
+    pub fn write_docs_to_disk(&self) -> io::Result<()>{ 
+        let bytes = bincode::encode_to_vec(&self.docs, bincode::config::standard())
+            .map_err(|err| Error::new(ErrorKind::InvalidData, format!("can not encode document table: {err}")))?;
+        let mut snapshot = Vec::with_capacity(DOCS_MAGIC.len() + 4 + bytes.len());
+        snapshot.extend_from_slice(DOCS_MAGIC);
+        snapshot.extend_from_slice(&DOCS_VERSION.to_le_bytes());
+        snapshot.extend_from_slice(&bytes);
+        atomic_write(&self.dir.join(DOCS_PATH), &snapshot)
+    }
+
+    
+
+    pub fn add_document(&mut self,  content: String, path: PathBuf) -> io::Result<()> { 
+        self.index_document(Document::new(path).field(DEFAULT_FIELD, content))
+    }
+
+    /// Indexes every field of `document` with the analyzer of that field,
+    /// after checking it against the schema if the index has one.
+    pub fn index_document(&mut self, document: Document) -> io::Result<()> { 
+        if self.docs.paths.contains_key(&document.path) { 
+            println!("already exists");
+            return Err(Error::new(ErrorKind::AlreadyExists, format!("document of path : {:?} already exists", document.path)))
+        }
+        self.insert_document(document).map(|_| ())
+    }
+
+    /// Indexes `document` when its path is new, and when it was indexed
+    /// before with different content replaces it: the new version goes in
+    /// under a new id before the old one is deleted, so a crash in between
+    /// leaves the old version searchable.
+    pub fn update_document(&mut self, document: Document) -> io::Result<Update> { 
+        match self.docs.paths.get(&document.path).copied() { 
+            None => self.insert_document(document).map(Update::Added),
+            Some((old, hash)) if hash == document.content_hash() => Ok(Update::Unchanged(old)),
+            Some((old, _)) => { 
+                let new = self.insert_document(document)?;
+                self.delete_document(old)?;
+                Ok(Update::Replaced { old, new })
+            }
+        }
+    }
+
+    fn insert_document(&mut self, document: Document) -> io::Result<DocumentId> { 
+        if let Some((field, _)) = document.fields.iter().find(|(field, _)| field.contains(':')) { 
+            return Err(Error::new(ErrorKind::InvalidInput, format!("field name {field:?} has a colon")))
+        }
+        if let Some(schema) = &self.schema { 
+            schema.validate(&document)?;
+            for (field, _) in &document.fields { 
+                if let Some(analyzer) = schema.get(field).and_then(|entry| entry.analyzer.as_ref())
+                    && self.analyzers.get(analyzer).is_none() { 
+                    return Err(SchemaError::UnknownAnalyzer { field: field.clone(), analyzer: analyzer.clone() }.into())
+                }
+            }
+        }
+        let doc_id = self.docs.current_doc_id;
+        let hash = document.content_hash();
+        let path = document.path;
+        println!("fields : {:?}, doc_id :{doc_id} and path: {path:?}", document.fields);
+        let mut positions: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut lengths: HashMap<&str, usize> = HashMap::new();
+        // where the next value of a field starts
+        let mut next_position: HashMap<&str, usize> = HashMap::new();
+        for (field, value) in &document.fields { 
+            let value = match &self.schema { 
+                Some(schema) if !schema.get(field).is_some_and(|entry| entry.indexed) => continue,
+                Some(schema) => schema.normalize(field, value)?,
+                None => value.clone()
+            };
+            let tokens = self.analyzer_for(field).analyze(&value);
+            *lengths.entry(field).or_default() += analysis::token_count(&tokens);
+            let start = next_position.get(field.as_str()).copied().unwrap_or(0);
+            let mut end = start;
+            for token in tokens { 
+                end = end.max(start + token.position + 1);
+                positions.entry(field_term(field, &token.text)).or_default().push(start + token.position);
+            }
+            next_position.insert(field, end + document::VALUE_POSITION_GAP);
+        }
+
+        let lengths = lengths.into_iter().map(|(field, length)| (field.to_string(), length)).collect();
+        // without a schema every field is stored
+        let stored: Vec<(String, String)> = document.fields.into_iter()
+            .filter(|(field, _)| self.schema.as_ref().is_none_or(|schema| schema.get(field).is_some_and(|entry| entry.stored)))
+            .collect();
+        let terms: Vec<String> = positions.keys().cloned().collect();
+        let record = DocumentRecord { 
+            doc_id,
+            path: path.clone(),
+            hash,
+            lengths,
+            fields: stored.clone(),
+            terms: terms.clone()
+        };
+        self.journal.log(record.to_record()?)?;
+        self.docs.insert(record);
+        self.store.add(doc_id, StoredDocument { document: Document { path, fields: stored }, terms })?;
+        for (word, word_positions) in positions.iter() { 
+            // only terms already loaded from the segments are cached, a fresh
+            // entry here would hide the postings written by earlier runs
+            if let Some(postings) = self.index.get_mut(word) { 
+                postings.push(Posting::new(doc_id, word_positions.clone()));
+            }
+        }
+        println!("index entry is {:?}", self.index);
+        
+        self.docs_count += 1;
+        for (term, word_positions) in positions { 
+            let mut written = 0;
+            for chunk in posting::encode_chunks(&[Posting::new(doc_id, word_positions)]) { 
+                if self.segment_store.write(term.clone(), &chunk).is_ok() { 
+                    written += 1;
+                }
+            }
+            self.dictionary.insert(&term, 1, written);
+        }
+        Ok(doc_id)
+    }
+
+    /// Deletes a document, given by id or path. It drops out of every search
+    /// and out of the term dictionary at once; its postings stay in the
+    /// segments, skipped, until `compact`.
+    pub fn delete_document<'a>(&mut self, document: impl Into<DocumentRef<'a>>) -> io::Result<DocumentId> { 
+        let document = document.into();
+        let doc_id = match document { 
+            DocumentRef::Id(doc_id) => self.docs.docs.contains_key(&doc_id).then_some(doc_id),
+            DocumentRef::Path(path) => self.docs.id_of(path)
+        }.ok_or_else(|| Error::new(ErrorKind::NotFound, format!("document {document:?} not found")))?;
+        let terms = self.store.terms(doc_id)?;
+        self.journal.log(format!("{},{}", journal::TOMBSTONE, doc_id))?;
+        self.docs.delete(doc_id);
+        self.docs_count -= 1;
+        self.dictionary.remove_document(&terms);
+        for postings in self.index.values_mut() { 
+            postings.retain(|posting| posting.doc_id != doc_id);
+        }
+        Ok(doc_id)
+    }
+
+    /// Rewrites the segments and the document store without the documents
+    /// deleted since the last compaction, and rebuilds the term dictionary
+    /// to match.
+    pub fn compact(&mut self) -> io::Result<()> { 
+        if self.docs.deleted.is_empty() { 
+            return Ok(())
+        }
+        let mut terms: Vec<String> = self.segment_store.terms().cloned().collect();
+        terms.sort_unstable();
+        let mut chunks = Vec::new();
+        let mut dictionary = TermDictionary::default();
+        for term in terms { 
+            let postings = self.read_postings(&term)?;
+            let encoded = posting::encode_chunks(&postings);
+            dictionary.insert(&term, postings.len(), encoded.len());
+            chunks.extend(encoded.into_iter().map(|chunk| (term.clone(), chunk)));
+        }
+        self.segment_store.rewrite(&chunks)?;
+        let docs = &self.docs.docs;
+        self.store.retain(&self.dir.join(STORE_PATH), |doc_id| docs.contains_key(&doc_id))?;
+        // until this is snapshotted the deleted documents are skipped as before
+        self.docs.deleted.clear();
+        self.checkpoint()?;
+        self.index.clear();
+        self.weights.clear();
+        self.dictionary = dictionary;
+        self.dictionary.save(&self.dir.join(DICTIONARY_PATH))
+    }
+
+    pub fn search(&mut self, term: String) -> Vec<&PathBuf>{ 
+        self.search_query(&Query::Term(term))
+    }
+
+    pub fn search_query(&mut self, query: &Query) -> Vec<&PathBuf> { 
+        let docs = self.evaluate(query);
+        docs.iter().filter_map(|doc| self.docs.docs.get(doc)).collect()
+    }
+
+    /// Replaces the analyzer used for indexing and querying. Documents already
+    /// indexed keep the terms the previous analyzer produced.
+    pub fn set_analyzer(&mut self, analyzer: impl Analyzer + 'static) { 
+        self.analyzer = Arc::new(analyzer);
+    }
+
+    /// Makes `analyzer` available to schemas under `name`.
+    pub fn register_analyzer(&mut self, name: &str, analyzer: impl Analyzer + 'static) { 
+        self.analyzers.register(name, analyzer);
+    }
+
+    /// Sets the analyzer of one field, taking precedence over the schema;
+    /// fields with neither use the index analyzer.
+    pub fn set_field_analyzer(&mut self, field: &str, analyzer: impl Analyzer + 'static) { 
+        self.field_analyzers.insert(field.to_string(), Arc::new(analyzer));
+    }
+
+    /// Sets the analyzer queries on one field go through, such as one adding
+    /// synonyms only at query time. Without one a field is searched with the
+    /// analyzer it is indexed with.
+    pub fn set_query_analyzer(&mut self, field: &str, analyzer: impl Analyzer + 'static) { 
+        self.query_analyzers.insert(field.to_string(), Arc::new(analyzer));
+    }
+
+    /// Scales the score of every match in `field` by `boost`.
+    pub fn set_field_boost(&mut self, field: &str, boost: f32) { 
+        self.field_boosts.insert(field.to_string(), boost);
+    }
+
+    fn analyzer_for(&self, field: &str) -> &Arc<dyn Analyzer> { 
+        if let Some(analyzer) = self.field_analyzers.get(field) { 
+            return analyzer
+        }
+        let entry = self.schema.as_ref().and_then(|schema| schema.get(field));
+        let name = match entry { 
+            Some(entry) if entry.field_type == FieldType::Text => entry.analyzer.as_deref(),
+            // keyword, integer and date values are single terms
+            Some(_) => Some("keyword"),
+            None => None
+        };
+        name.and_then(|name| self.analyzers.get(name)).unwrap_or(&self.analyzer)
+    }
+
+    fn query_analyzer_for(&self, field: &str) -> &Arc<dyn Analyzer> { 
+        self.query_analyzers.get(field).unwrap_or_else(|| self.analyzer_for(field))
+    }
+
+    /// Splits a segment key into its field and term.
+    fn split_field<'a>(&self, key: &'a str) -> (&'a str, &'a str) { 
+        split_field_term(key)
+    }
+
+    /// Replaces the similarity used by `search_ranked`.
+    pub fn set_similarity(&mut self, similarity: impl Similarity + 'static) { 
+        self.similarity = Arc::new(similarity);
+    }
+
+    /// Runs `query` and ranks the matching documents with the index similarity, best first.
+    pub fn search_ranked(&mut self, query: &Query) -> Vec<(&PathBuf, f32)> { 
+        let similarity = self.similarity.clone();
+        self.search_ranked_with(query, similarity.as_ref())
+    }
+
+    /// Like `search_ranked`, but scores this one query with `similarity`.
+    pub fn search_ranked_with(&mut self, query: &Query, similarity: &dyn Similarity) -> Vec<(&PathBuf, f32)> { 
+        let Some(query) = self.analyze_query(query) else { 
+            return vec![]
+        };
+        let docs = self.matches(&query);
+        let terms = self.scoring_postings(&query);
+        let scorer = Scorer::new(&self.docs, terms, similarity);
+        let mut scored: Vec<(DocumentId, f32)> = docs.into_iter().map(|doc| (doc, scorer.score(doc))).collect();
+        scored.sort_by(|(left_doc, left), (right_doc, right)| right.total_cmp(left).then(left_doc.cmp(right_doc)));
+        scored.into_iter()
+            .filter_map(|(doc, score)| self.docs.docs.get(&doc).map(|path| (path, score)))
+            .collect()
+    }
+
+    /// Returns one page of the ranked results of `query`: `limit` hits after
+    /// skipping `offset`, counted from the hit after `after` when a cursor
+    /// from the previous page is given. Matches are scored as the query
+    /// yields them and only `offset + limit` hits are kept, so the matching
+    /// documents are never listed or sorted as a whole.
+    pub fn search_page(&mut self, query: &Query, offset: usize, limit: usize, after: Option<Cursor>) -> SearchPage { 
+        let similarity = self.similarity.clone();
+        let Some(query) = self.analyze_query(query) else { 
+            return SearchPage { total: 0, hits: vec![] }
+        };
+        let docs = self.doc_set(&query);
+        let terms = self.scoring_postings(&query);
+        let scorer = Scorer::new(&self.docs, terms, similarity.as_ref());
+        let mut top = TopDocs::new(offset.saturating_add(limit), after);
+        let mut total = 0;
+        for doc in docs { 
+            top.collect(doc, scorer.score(doc));
+            total += 1;
+        }
+        let hits = top.into_sorted().into_iter()
+            .skip(offset)
+            .filter_map(|(doc_id, score)| self.docs.docs.get(&doc_id).map(|path| Hit { doc_id, path: path.clone(), score }))
+            .collect();
+        SearchPage { total, hits }
+    }
+
+    /// The stored fields of a document, as kept in the document store.
+    pub fn document(&self, doc_id: DocumentId) -> io::Result<Option<Document>> { 
+        if !self.docs.docs.contains_key(&doc_id) { 
+            return Ok(None)
+        }
+        self.store.get(doc_id)
+    }
+
+    /// Returns the fragments of a matched document's default field that best
+    /// show the terms of `query`, highlighted. The field is taken from the
+    /// document store, or read from the document path when it is not stored.
+    pub fn highlight(&self, query: &Query, doc_id: DocumentId, highlighter: &Highlighter) -> io::Result<Vec<String>> { 
+        let path = self.docs.docs.get(&doc_id)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("document {doc_id} not found")))?;
+        let stored: Vec<String> = self.document(doc_id)?
+            .map(|document| document.values(DEFAULT_FIELD).map(str::to_string).collect())
+            .unwrap_or_default();
+        let content = if stored.is_empty() { std::fs::read_to_string(path)? } else { stored.join("\n") };
+        let terms: HashSet<String> = self.analyze_query(query)
+            .map(|query| self.scoring_terms(&query).into_iter()
+                .filter_map(|(term, _)| match self.split_field(&term) { 
+                    (DEFAULT_FIELD, term) => Some(term.to_string()),
+                    _ => None
+                })
+                .collect())
+            .unwrap_or_default();
+        Ok(highlighter.fragments(&content, &self.analyzer_for(DEFAULT_FIELD).analyze(&content), &terms))
+    }
+
+    /// The postings of every positive query term, with the weight its score
+    /// is scaled by and its field, for a `Scorer`.
+    fn scoring_postings(&mut self, query: &Query) -> Vec<(Vec<Posting>, f32, String)> { 
+        self.scoring_terms(query).into_iter().map(|(term, weight)| { 
+            let field = self.split_field(&term).0.to_string();
+            let weight = weight * self.field_boosts.get(&field).copied().unwrap_or(1.0);
+            (self.postings(&term), weight, field)
+        }).collect()
+    }
+
+    /// Evaluates a query into a sorted, deduplicated list of document ids.
+    pub fn evaluate(&mut self, query: &Query) -> Vec<DocumentId> { 
+        match self.analyze_query(query) { 
+            Some(query) => self.matches(&query),
+            None => vec![]
+        }
+    }
+
+    /// Runs the text of `query` through the analyzers of the fields it
+    /// targets. A term that analyzes to several tokens becomes a phrase, or
+    /// alternatives when they share one position or one of them spans the
+    /// positions of others, as with multi-word synonyms; clauses left without any
+    /// term are dropped, and `None` means nothing of the query is left to
+    /// search for. Field clauses are folded into their terms, which come out
+    /// as the keys they are stored under, see `document::field_term`.
+    pub fn analyze_query(&self, query: &Query) -> Option<Query> { 
+        self.analyze_field(query, DEFAULT_FIELD)
+    }
+
+    fn analyze_field(&self, query: &Query, field: &str) -> Option<Query> { 
+        let analyze_all = |clauses: &[Query]| -> Vec<Query> { 
+            clauses.iter().filter_map(|clause| self.analyze_field(clause, field)).collect()
+        };
+        let normalize = |term: &str| field_term(field, &self.query_analyzer_for(field).normalize(term));
+        let analyzed = match query { 
+            Query::Term(text) => self.analyze_text(text, field, 0)?,
+            Query::Phrase { terms, slop, .. } => self.analyze_text(&terms.join(" "), field, *slop)?,
+            Query::Prefix(prefix) => Query::Prefix(normalize(prefix)),
+            Query::Wildcard(pattern) => Query::Wildcard(normalize(pattern)),
+            Query::Fuzzy { term, distance, transpositions } => Query::Fuzzy { term: normalize(term), distance: *distance, transpositions: *transpositions },
+            Query::And(clauses) => Query::And(analyze_all(clauses)),
+            Query::Or(clauses) => Query::Or(analyze_all(clauses)),
+            Query::Not(inner) => Query::not(self.analyze_field(inner, field)?),
+            Query::Bool { must, should, must_not } => { 
+                let (must, should) = (analyze_all(must), analyze_all(should));
+                if must.is_empty() && should.is_empty() { 
+                    return None
+                }
+                Query::Bool { must, should, must_not: analyze_all(must_not) }
+            },
+            Query::Field { field, query } => self.analyze_field(query, field)?,
+            Query::Boost { query, boost } => Query::Boost { query: Box::new(self.analyze_field(query, field)?), boost: *boost }
+        };
+        match analyzed { 
+            Query::And(clauses) | Query::Or(clauses) if clauses.is_empty() => None,
+            analyzed => Some(analyzed)
+        }
+    }
+
+    fn analyze_text(&self, text: &str, field: &str, slop: usize) -> Option<Query> { 
+        // integers and dates are searched in the form they were indexed in
+        let normalized = self.schema.as_ref().and_then(|schema| schema.normalize(field, text).ok());
+        let tokens = self.query_analyzer_for(field).analyze(normalized.as_deref().unwrap_or(text));
+        let first = tokens.iter().map(|token| token.position).min()?;
+        let end = tokens.iter().map(|token| token.position + token.length).max()?;
+        // a token spanning several positions is an alternative to the words
+        // under it, each way through the tokens is searched for on its own
+        let mut alternatives: Vec<Query> = analysis::token_paths(&tokens, first, end).into_iter()
+            .map(|path| { 
+                if path.iter().all(|(_, position)| *position == 0) { 
+                    let mut terms: Vec<Query> = path.into_iter().map(|(term, _)| Query::Term(field_term(field, &term))).collect();
+                    return if terms.len() == 1 { terms.remove(0) } else { Query::Or(terms) }
+                }
+                let (terms, positions) = path.into_iter().map(|(term, position)| (field_term(field, &term), position)).unzip();
+                Query::Phrase { terms, positions, slop }
+            })
+            .collect();
+        Some(if alternatives.len() == 1 { alternatives.remove(0) } else { Query::Or(alternatives) })
+    }
+
+    /// Evaluates an analyzed query.
+    fn matches(&mut self, query: &Query) -> Vec<DocumentId> { 
+        self.doc_set(query).collect()
+    }
+
+    /// The documents matching an analyzed query, to go through one at a time.
+    fn doc_set(&mut self, query: &Query) -> DocSet { 
+        match query { 
+            Query::Term(term) => DocSet::postings(self.postings(term)),
+            Query::Phrase { terms, positions, slop } => self.phrase(terms, positions, *slop),
+            Query::Prefix(_) | Query::Wildcard(_) | Query::Fuzzy { .. } => { 
+                let sets = self.expand(query).into_iter().map(|(term, _)| DocSet::postings(self.postings(&term))).collect();
+                DocSet::union(sets)
+            },
+            Query::And(clauses) => { 
+                let mut positives = Vec::new();
+                let mut negatives = Vec::new();
+                for clause in clauses { 
+                    match clause { 
+                        Query::Not(inner) => negatives.push(self.doc_set(inner)),
+                        _ => positives.push(self.doc_set(clause))
+                    }
+                }
+                let docs = if positives.is_empty() { DocSet::ids(self.all_documents()) } else { DocSet::intersection(positives) };
+                DocSet::difference(docs, DocSet::union(negatives))
+            },
+            Query::Or(clauses) => DocSet::union(clauses.iter().map(|clause| self.doc_set(clause)).collect()),
+            Query::Not(inner) => { 
+                let excluded = self.doc_set(inner);
+                DocSet::difference(DocSet::ids(self.all_documents()), excluded)
+            },
+            Query::Bool { must, should, must_not } => { 
+                let docs = if must.is_empty() { 
+                    DocSet::union(should.iter().map(|clause| self.doc_set(clause)).collect())
+                } else { 
+                    DocSet::intersection(must.iter().map(|clause| self.doc_set(clause)).collect())
+                };
+                let excluded = must_not.iter().map(|clause| self.doc_set(clause)).collect();
+                DocSet::difference(docs, DocSet::union(excluded))
+            },
+            // analysis folds fields into the terms
+            Query::Field { query, .. } | Query::Boost { query, .. } => self.doc_set(query)
+        }
+    }
+
+    /// Proposes up to `n` indexed terms close to `term`, nearest first and
+    /// the more common of equally near terms first. `term` is normalized like
+    /// a fuzzy query on the default field, lowercased and folded.
+    pub fn suggest(&self, term: &str, n: usize) -> Vec<String> { 
+        let term = &self.query_analyzer_for(DEFAULT_FIELD).normalize(term);
+        let max_distance = if term.chars().count() <= 3 { 1 } else { 2 };
+        let automaton = LevenshteinAutomaton::new(term, max_distance, true);
+        let qualifier = field_term(DEFAULT_FIELD, "");
+        let terms = self.dictionary.with_prefix(&qualifier).map(|(key, _)| &key[qualifier.len()..]);
+        let mut candidates: Vec<(&str, usize, usize)> = automaton.matches(terms)
+            .into_iter()
+            .filter(|(_, distance)| *distance > 0)
+            .map(|(candidate, distance)| (candidate, distance, self.dictionary.doc_freq(&field_term(DEFAULT_FIELD, candidate))))
+            .collect();
+        candidates.sort_by(|left, right| left.1.cmp(&right.1).then(right.2.cmp(&left.2)).then(left.0.cmp(right.0)));
+        candidates.into_iter().take(n).map(|(candidate, _, _)| candidate.to_string()).collect()
+    }
+
+    /// Completes `prefix` to the `n` indexed terms found in the most
+    /// documents. A `field:` prefix completes terms of that field, given as
+    /// `field:term`; terms of the default field come without their field.
+    /// The prefix is normalized like a prefix query on the field.
+    pub fn complete(&self, prefix: &str, n: usize) -> Vec<String> { 
+        let (field, term) = self.split_field(prefix);
+        let term = self.query_analyzer_for(field).normalize(term);
+        self.dictionary.complete(&field_term(field, &term), n)
+            .into_iter()
+            .map(|(key, _)| match self.split_field(&key) { 
+                (DEFAULT_FIELD, term) => term.to_string(),
+                _ => key
+            })
+            .collect()
+    }
+
+    /// Terms that contribute to relevance with the weight their score is
+    /// scaled by, multi-term queries expanded; anything under a `Not` is left out.
+    fn scoring_terms(&self, query: &Query) -> Vec<(String, f32)> { 
+        let mut terms = match query { 
+            Query::Term(term) => vec![(term.clone(), 1.0)],
+            Query::Phrase { terms, .. } => terms.iter().map(|term| (term.clone(), 1.0)).collect(),
+            Query::Prefix(_) | Query::Wildcard(_) | Query::Fuzzy { .. } => self.expand(query),
+            Query::And(clauses) | Query::Or(clauses) => clauses.iter().flat_map(|clause| self.scoring_terms(clause)).collect(),
+            Query::Bool { must, should, .. } => must.iter().chain(should).flat_map(|clause| self.scoring_terms(clause)).collect(),
+            Query::Field { query, .. } => self.scoring_terms(query),
+            Query::Boost { query, boost } => self.scoring_terms(query).into_iter().map(|(term, weight)| (term, weight * boost)).collect(),
+            Query::Not(_) => vec![]
+        };
+        // a term reached through several clauses counts once, at its best weight
+        terms.sort_by(|(left, left_weight), (right, right_weight)| left.cmp(right).then(right_weight.total_cmp(left_weight)));
+        terms.dedup_by(|(next, _), (kept, _)| next == kept);
+        terms
+    }
+
+    /// Expands an analyzed prefix, wildcard or fuzzy query to the sorted
+    /// dictionary terms of its field it matches. Fuzzy matches are weighted
+    /// down the more edits they need.
+    pub fn expand(&self, query: &Query) -> Vec<(String, f32)> { 
+        let same_field = |key: &str, term: &str| self.split_field(term).0 == self.split_field(key).0;
+        let terms: Vec<String> = match query { 
+            Query::Prefix(prefix) => self.dictionary.with_prefix(prefix)
+                .filter(|(term, _)| same_field(prefix, term))
+                .map(|(term, _)| term.to_string())
+                .collect(),
+            Query::Wildcard(pattern) => { 
+                // the literal part before the first wildcard narrows the dictionary range
+                let literal = &pattern[..pattern.find(['*', '?']).unwrap_or(pattern.len())];
+                self.dictionary.with_prefix(literal)
+                    .filter(|(term, _)| same_field(pattern, term) && query::wildcard_matches(pattern, term))
+                    .map(|(term, _)| term.to_string())
+                    .collect()
+            },
+            Query::Fuzzy { term, distance, transpositions } => { 
+                // edits only count in the term, never in the field name
+                let (field, term) = self.split_field(term);
+                let automaton = LevenshteinAutomaton::new(term, *distance, *transpositions);
+                let len = term.chars().count() as f32;
+                let qualifier = field_term(field, "");
+                let candidates = self.dictionary.with_prefix(&qualifier)
+                    .filter(|(candidate, _)| self.split_field(candidate).0 == field)
+                    .map(|(candidate, _)| &candidate[qualifier.len()..]);
+                return automaton.matches(candidates)
+                    .into_iter()
+                    .map(|(matched, edits)| (field_term(field, matched), 1.0 - edits as f32 / (len + 1.0)))
+                    .collect()
+            },
+            _ => return vec![]
+        };
+        terms.into_iter().map(|term| (term, 1.0)).collect()
+    }
+
+    fn phrase(&mut self, terms: &[String], positions: &[usize], slop: usize) -> DocSet { 
+        // terms on the same position are alternatives, so each position gets
+        // the postings of all its terms folded together
+        let mut offsets: Vec<usize> = positions.to_vec();
+        offsets.sort_unstable();
+        offsets.dedup();
+        let mut lists: Vec<Vec<Posting>> = Vec::with_capacity(offsets.len());
+        for offset in &offsets { 
+            let mut postings = Vec::new();
+            for (term, _) in terms.iter().zip(positions).filter(|(_, position)| *position == offset) { 
+                postings.extend(self.postings(term));
+            }
+            lists.push(posting::merge(postings));
+        }
+        DocSet::phrase(lists, offsets, slop)
+    }
+
+    fn all_documents(&self) -> Vec<DocumentId> { 
+        let mut docs: Vec<DocumentId> = self.docs.docs.keys().copied().collect();
+        docs.sort_unstable();
+        docs
+    }
+
+    /// Returns the postings of `term` sorted by document, loading them from the segments on a cache miss.
+    pub fn postings(&mut self, term: &str) -> Vec<Posting> { 
+        self.last_used += 1;
+        let last_used = self.last_used;
+        self.weights.insert(term.to_string(), last_used as usize);
+        if let Some(postings) = self.index.get(term) {
+            println!("postings in index : {:?}", postings);
+            return postings.clone();
+        }
+        if !self.segment_store.contains(term) { 
+            return vec![]
+        }
+        if self.index.len()  +  1 > self.cap { 
+            self.evict();
+        }
+        match self.read_postings(term) { 
+            Ok(postings) => { 
+                self.index.insert(term.to_string(), postings.clone());
+                postings
+            },
+            Err(err) => { 
+                eprintln!("can not read the postings of {term:?}: {err}");
+                vec![]
+            }
+        }
+    }
+
+    /// Reads the postings of `term` from the segments, leaving out deleted documents.
+    fn read_postings(&mut self, term: &str) -> io::Result<Vec<Posting>> { 
+        let Ok(bytes_vec) = self.segment_store.read_bytes(term.to_string()) else { 
+            return Ok(vec![])
+        };
+        let mut postings = Vec::new();
+        for bytes in bytes_vec { 
+            let chunk = posting::decode_chunk(&bytes)?;
+            println!("postings comming from disk ; {:?}", chunk);
+            postings.extend(chunk);
+        } 
+        let mut postings = posting::merge(postings);
+        postings.retain(|posting| !self.docs.deleted.contains(&posting.doc_id));
+        Ok(postings)
+    }
+}
+
+#[test]
+pub fn test_document_table() { 
+    let mut docs = WritableDocs::default();
+    for (doc_id, path) in [(0, "a.txt"), (1, "b.txt"), (2, "a.txt")] { 
+        docs.insert(DocumentRecord { 
+            doc_id,
+            path: PathBuf::from(path),
+            hash: doc_id as u64,
+            lengths: vec![(DEFAULT_FIELD.to_string(), 3)],
+            fields: Vec::new(),
+            terms: Vec::new()
+        });
+    }
+    // the newer version of a path wins
+    assert_eq!(docs.id_of(Path::new("a.txt")), Some(2));
+    assert_eq!(docs.id_of(Path::new("c.txt")), None);
+    assert_eq!(docs.current_doc_id, 3);
+
+    let bytes = bincode::encode_to_vec(&docs, bincode::config::standard()).unwrap();
+    let (loaded, _): (WritableDocs, _) = bincode::decode_from_slice(&bytes, bincode::config::standard()).unwrap();
+    assert_eq!(loaded.paths.get(Path::new("b.txt")), Some(&(1, 1)));
+    assert_eq!(loaded.lengths[DEFAULT_FIELD].total, 9);
+}
+
+#[cfg(test)]
+fn temp_index_dir(name: &str) -> PathBuf { 
+    let dir = std::env::temp_dir().join(format!("rusterine-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+pub fn test_delete_document() { 
+    let dir = temp_index_dir("delete");
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    index.add_document("rust is fast".to_string(), PathBuf::from("a.txt")).unwrap();
+    index.add_document("rust is safe".to_string(), PathBuf::from("b.txt")).unwrap();
+    index.add_document("java is verbose".to_string(), PathBuf::from("c.txt")).unwrap();
+    let rust = Query::Term("rust".to_string());
+    let a = PathBuf::from("a.txt");
+    assert_eq!(index.search_query(&rust).len(), 2);
+    assert_eq!(index.delete_document(Path::new("b.txt")).unwrap(), 1);
+    assert_eq!(index.search_query(&rust), vec![&a]);
+    let page = index.search_page(&rust, 0, 10, None);
+    assert_eq!((page.total, page.hits.len()), (1, 1));
+    assert_eq!(index.delete_document(1).unwrap_err().kind(), ErrorKind::NotFound);
+    // terms only the deleted document had are no longer offered
+    assert!(index.complete("sa", 5).is_empty());
+    assert_eq!(index.dictionary.doc_freq("content:rust"), 1);
+    drop(index);
+
+    // the tombstone is in the WAL, nothing was synced
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    assert_eq!(index.search_query(&rust), vec![&a]);
+    // the stored fields come back from the journal
+    assert_eq!(index.document(0).unwrap().unwrap().values(DEFAULT_FIELD).next(), Some("rust is fast"));
+    assert_eq!(index.document(1).unwrap(), None);
+    assert_eq!(index.dictionary.doc_freq("content:safe"), 0);
+    index.compact().unwrap();
+    assert!(index.docs.deleted.is_empty());
+    assert_eq!(index.search_query(&rust), vec![&a]);
+    assert!(index.complete("sa", 5).is_empty());
+    drop(index);
+
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    assert_eq!(index.search_query(&rust), vec![&a]);
+    assert_eq!(index.search_query(&Query::Term("java".to_string())), vec![&PathBuf::from("c.txt")]);
+    assert_eq!(index.dictionary.doc_freq("content:rust"), 1);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+pub fn test_document_journal() { 
+    let dir = temp_index_dir("journal");
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    index.add_document("rust is fast".to_string(), PathBuf::from("a.txt")).unwrap();
+    index.add_document("rust is safe".to_string(), PathBuf::from("b.txt")).unwrap();
+    index.delete_document(0).unwrap();
+    assert_eq!(index.journal.read_records().len(), 3);
+    // the snapshot covers the journal from here on
+    index.sync().unwrap();
+    assert!(index.journal.read_records().is_empty());
+    index.add_document("rust is fun".to_string(), PathBuf::from("c.txt")).unwrap();
+    drop(index);
+
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    assert_eq!(index.docs.current_doc_id, 3);
+    assert_eq!(index.docs.deleted, HashSet::from([0]));
+    assert_eq!(index.search_query(&Query::Term("rust".to_string())).len(), 2);
+    index.compact().unwrap();
+    assert!(index.journal.read_records().is_empty());
+    drop(index);
+
+    // snapshots from before the table had a version only know ids and paths
+    #[derive(Encode)]
+    struct Legacy { docs: HashMap<DocumentId, PathBuf>, current_doc_id: usize }
+    let legacy = Legacy { docs: HashMap::from([(1, PathBuf::from("b.txt"))]), current_doc_id: 2 };
+    std::fs::write(dir.join(DOCS_PATH), bincode::encode_to_vec(legacy, bincode::config::standard()).unwrap()).unwrap();
+    let (docs, version) = InvertedIndex::load_docs_from_disk(&dir).unwrap();
+    assert_eq!(version, 0);
+    assert_eq!(docs.id_of(Path::new("b.txt")), Some(1));
+    assert_eq!(docs.current_doc_id, 2);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+pub fn test_update_document() { 
+    let dir = temp_index_dir("update");
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    let a = PathBuf::from("a.txt");
+    let document = |content: &str| Document::new("a.txt").field(DEFAULT_FIELD, content);
+    assert_eq!(index.update_document(document("rust is fast")).unwrap(), Update::Added(0));
+    assert_eq!(index.update_document(document("rust is fast")).unwrap(), Update::Unchanged(0));
+    assert_eq!(index.update_document(document("rust is safe")).unwrap(), Update::Replaced { old: 0, new: 1 });
+    assert_eq!(index.docs.id_of(&a), Some(1));
+    assert!(index.search_query(&Query::Term("fast".to_string())).is_empty());
+    assert_eq!(index.search_query(&Query::Term("safe".to_string())), vec![&a]);
+    assert_eq!(index.docs_count, 1);
+
+    // a replace cut short: the new version is in, the old one not deleted
+    index.insert_document(document("rust is fun")).unwrap();
+    assert!(index.docs.docs.contains_key(&1));
+    drop(index);
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    assert!(!index.docs.docs.contains_key(&1));
+    assert_eq!(index.docs.id_of(&a), Some(2));
+    assert_eq!(index.search_query(&Query::Term("rust".to_string())), vec![&a]);
+    assert!(index.search_query(&Query::Term("safe".to_string())).is_empty());
+    assert_eq!(index.update_document(document("rust is fun")).unwrap(), Update::Unchanged(2));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+pub fn test_keyword_line_breaks() { 
+    let dir = temp_index_dir("keyword");
+    let tag = "a\n#tombstone,0\nb";
+    let by_tag = Query::Field { field: "tag".to_string(), query: Box::new(Query::Term(tag.to_string())) };
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    index.set_field_analyzer("tag", TextAnalyzer::new(analysis::KeywordTokenizer));
+    index.index_document(Document::new("a.txt").field("tag", tag).field(DEFAULT_FIELD, "rust")).unwrap();
+    index.add_document("rust again".to_string(), PathBuf::from("b.txt")).unwrap();
+    drop(index);
+
+    // the term did not break its WAL record apart
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    index.set_field_analyzer("tag", TextAnalyzer::new(analysis::KeywordTokenizer));
+    assert_eq!(index.docs_count, 2);
+    assert_eq!(index.search_query(&by_tag), vec![&PathBuf::from("a.txt")]);
+    assert_eq!(index.search_query(&Query::Term("rust".to_string())).len(), 2);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+pub fn test_accent_folding() { 
+    let dir = temp_index_dir("folding");
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    index.add_document("un café crème".to_string(), PathBuf::from("a.txt")).unwrap();
+    let a = PathBuf::from("a.txt");
+    assert_eq!(index.search("cafe".to_string()), vec![&a]);
+    assert_eq!(index.search_query(&Query::parse("Café AND creme").unwrap()), vec![&a]);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+pub fn test_query_analyzer() { 
+    use analysis::SynonymFilter;
+    use synonyms::SynonymMap;
+
+    let dir = temp_index_dir("query-analyzer");
+    let synonyms = || TextAnalyzer::default().filter(SynonymFilter::new(SynonymMap::parse("car, automobile", true).unwrap()));
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    // synonyms added to queries on the content, and to the indexed titles only
+    index.set_query_analyzer(DEFAULT_FIELD, synonyms());
+    index.set_field_analyzer("title", synonyms());
+    index.set_query_analyzer("title", TextAnalyzer::default());
+    index.index_document(Document::new("a.txt").field(DEFAULT_FIELD, "automobile for sale").field("title", "car")).unwrap();
+    let a = PathBuf::from("a.txt");
+    assert_eq!(index.search("car".to_string()), vec![&a]);
+    assert_eq!(index.search_query(&Query::parse("title:automobile").unwrap()), vec![&a]);
+    assert!(index.search_query(&Query::parse("title:sale").unwrap()).is_empty());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+pub fn test_multi_word_synonyms() { 
+    use analysis::SynonymFilter;
+    use synonyms::SynonymMap;
+
+    let dir = temp_index_dir("multi-word-synonyms");
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    let map = SynonymMap::parse("usa => united states of america", true).unwrap();
+    index.set_query_analyzer(DEFAULT_FIELD, TextAnalyzer::default().filter(SynonymFilter::new(map).preserve_original(true)));
+    for (path, content) in [("a.txt", "made in usa"), ("b.txt", "made in the united states of america"), ("c.txt", "states of the union")] { 
+        index.add_document(content.to_string(), PathBuf::from(path)).unwrap();
+    }
+    let (a, b) = (PathBuf::from("a.txt"), PathBuf::from("b.txt"));
+    let phrase = |terms: &[&str]| Query::Phrase { 
+        terms: terms.iter().map(|term| field_term(DEFAULT_FIELD, term)).collect(),
+        positions: (0..terms.len()).collect(),
+        slop: 0
+    };
+    assert_eq!(index.analyze_query(&Query::parse("usa").unwrap()), Some(Query::Or(vec![
+        Query::Term(field_term(DEFAULT_FIELD, "usa")),
+        phrase(&["united", "states", "of", "america"])
+    ])));
+    let mut found = index.search("usa".to_string());
+    found.sort();
+    assert_eq!(found, vec![&a, &b]);
+    // words after the synonym follow either alternative
+    assert_eq!(index.search_query(&Query::parse("\"in usa\"").unwrap()), vec![&a]);
+    assert_eq!(index.search_query(&Query::parse("\"the usa\"").unwrap()), vec![&b]);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+pub fn test_suggest() { 
+    let dir = temp_index_dir("suggest");
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    for (path, content) in [("a.txt", "rust"), ("b.txt", "rust"), ("c.txt", "rusty"), ("d.txt", "rusty"), ("e.txt", "bust"), ("f.txt", "rustacean")] { 
+        index.add_document(content.to_string(), PathBuf::from(path)).unwrap();
+    }
+    let expected: Vec<String> = ["rust", "rusty", "bust"].map(String::from).to_vec();
+    // one transposition away, then two edits with the more common term first
+    assert_eq!(index.suggest("rsut", 5), expected);
+    assert_eq!(index.suggest("Rsut", 5), expected);
+    assert_eq!(index.suggest("RÛTS", 5), expected);
+    assert_eq!(index.suggest("Rsut", 1), vec!["rust".to_string()]);
+    // short terms get a single edit
+    assert_eq!(index.suggest("Rst", 5), vec!["rust".to_string()]);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+pub fn test_complete() { 
+    let dir = temp_index_dir("complete");
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    for (path, content) in [("a.txt", "language lambda"), ("b.txt", "Language"), ("c.txt", "école élan"), ("d.txt", "Élan")] { 
+        index.add_document(content.to_string(), PathBuf::from(path)).unwrap();
+    }
+    assert_eq!(index.complete("la", 5), vec!["language".to_string(), "lambda".to_string()]);
+    assert_eq!(index.complete("La", 5), index.complete("la", 5));
+    assert_eq!(index.complete("LAN", 5), vec!["language".to_string()]);
+    // accented prefixes are folded like the indexed terms
+    assert_eq!(index.complete("é", 5), vec!["elan".to_string(), "ecole".to_string()]);
+    assert_eq!(index.complete("E\u{301}l", 5), vec!["elan".to_string()]);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+pub fn test_search_page() { 
+    let dir = temp_index_dir("page");
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    for (path, content) in [("a.txt", "rust"), ("b.txt", "rust rust go"), ("c.txt", "go"), ("d.txt", "rust and go and c")] { 
+        index.add_document(content.to_string(), PathBuf::from(path)).unwrap();
+    }
+    let query = Query::parse("rust OR go").unwrap();
+    let ranked: Vec<(PathBuf, f32)> = index.search_ranked(&query).into_iter().map(|(path, score)| (path.clone(), score)).collect();
+    let first = index.search_page(&query, 0, 2, None);
+    assert_eq!(first.total, 4);
+    let second = index.search_page(&query, 0, 2, first.hits.last().map(Hit::cursor));
+    let paged: Vec<(PathBuf, f32)> = first.hits.into_iter().chain(second.hits).map(|hit| (hit.path, hit.score)).collect();
+    assert_eq!(paged, ranked);
+    assert_eq!(index.search_page(&query, 3, 2, None).hits.len(), 1);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+pub fn test_search_page_ties() { 
+    let dir = temp_index_dir("page-ties");
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    // documents of the same content score the same
+    for i in 0..11 { 
+        let content = if i % 3 == 0 { "rust go" } else { "rust" };
+        index.add_document(content.to_string(), PathBuf::from(format!("{i}.txt"))).unwrap();
+    }
+    index.add_document("go".to_string(), PathBuf::from("go.txt")).unwrap();
+    let query = Query::parse("rust AND NOT java").unwrap();
+    let mut unpaged: Vec<PathBuf> = index.search_ranked(&query).into_iter().map(|(path, _)| path.clone()).collect();
+    let mut paged = Vec::new();
+    let mut after = None;
+    loop { 
+        let page = index.search_page(&query, 0, 2, after);
+        assert_eq!(page.total, 11);
+        let Some(last) = page.hits.last() else { 
+            break
+        };
+        after = Some(last.cursor());
+        paged.extend(page.hits.into_iter().map(|hit| hit.path));
+    }
+    // ties come in id order, so no page repeats or skips a document
+    assert_eq!(paged, unpaged);
+    paged.sort_unstable();
+    paged.dedup();
+    unpaged.sort_unstable();
+    assert_eq!(paged, unpaged);
+    assert_eq!(paged.len(), 11);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+pub fn test_many_documents() { 
+    let dir = temp_index_dir("many");
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    let words = ["rust", "memory", "safety", "speed", "borrow", "checker", "trait", "macro"];
+    for i in 0..80 { 
+        let content: Vec<String> = (0..40).map(|j| format!("{}{}", words[(i + j) % words.len()], j % 5)).collect();
+        index.add_document(content.join(" "), PathBuf::from(format!("{i}.txt"))).unwrap();
+    }
+    assert_eq!(index.search("rust0".to_string()).len(), 80);
+    index.sync().unwrap();
+    drop(index);
+
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    assert_eq!(index.search("rust0".to_string()).len(), 80);
+    assert_eq!(index.search_query(&Query::parse("\"rust0 memory1\"").unwrap()).len(), 80);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+pub fn test_legacy_postings() { 
+    let dir = temp_index_dir("legacy-postings");
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    index.add_document("rust".to_string(), PathBuf::from("a.txt")).unwrap();
+    // postings of a term written before they had positions
+    let legacy = bincode::encode_to_vec(vec![0usize], bincode::config::standard()).unwrap();
+    index.segment_store.write("content:old".to_string(), &legacy).unwrap();
+    drop(index);
+
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    let a = PathBuf::from("a.txt");
+    assert_eq!(index.search("old".to_string()), vec![&a]);
+    assert!(index.search_ranked(&Query::Term("old".to_string()))[0].1 > 0.0);
+    assert!(index.search_query(&Query::parse("\"old rust\"").unwrap()).is_empty());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+pub fn test_field_keys() { 
+    let dir = temp_index_dir("field-keys");
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    // a content token that looks like a term of another field
+    index.set_field_analyzer(DEFAULT_FIELD, TextAnalyzer::new(analysis::KeywordTokenizer));
+    index.index_document(Document::new("a.txt").field(DEFAULT_FIELD, "title:rust")).unwrap();
+    index.index_document(Document::new("b.txt").field("title", "rust")).unwrap();
+    let in_title = Query::Field { field: "title".to_string(), query: Box::new(Query::Term("rust".to_string())) };
+    assert_eq!(index.search_query(&in_title), vec![&PathBuf::from("b.txt")]);
+    assert_eq!(index.search("title:rust".to_string()), vec![&PathBuf::from("a.txt")]);
+    assert_eq!(index.complete("ti", 5), vec!["title:rust".to_string()]);
+    assert_eq!(index.complete("title:", 5), vec!["title:rust".to_string()]);
+    assert!(index.index_document(Document::new("c.txt").field("a:b", "rust")).is_err());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+pub fn test_qualify_legacy_keys() { 
+    let dir = temp_index_dir("legacy-keys");
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    index.add_document("rust is fast".to_string(), PathBuf::from("a.txt")).unwrap();
+    index.index_document(Document::new("b.txt").field("title", "rust")).unwrap();
+    // store the default field under bare keys, as version 1 did
+    let bare = |key: &str| key.strip_prefix("content:").unwrap_or(key).to_string();
+    let mut terms: Vec<String> = index.segment_store.terms().cloned().collect();
+    terms.sort_unstable();
+    let mut chunks = Vec::new();
+    for term in terms { 
+        chunks.extend(index.segment_store.read_bytes(term.clone()).unwrap().into_iter().map(|chunk| (bare(&term), chunk)));
+    }
+    index.segment_store.rewrite(&chunks).unwrap();
+    index.store.rewrite(&dir.join(STORE_PATH), |_, mut document| { 
+        document.terms = document.terms.iter().map(|term| bare(term)).collect();
+        Some(document)
+    }).unwrap();
+    index.sync().unwrap();
+    let mut snapshot = std::fs::read(dir.join(DOCS_PATH)).unwrap();
+    snapshot[4..8].copy_from_slice(&1u32.to_le_bytes());
+    std::fs::write(dir.join(DOCS_PATH), snapshot).unwrap();
+    drop(index);
+
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    assert!(index.segment_store.contains("content:rust") && !index.segment_store.contains("rust"));
+    assert_eq!(index.search("fast".to_string()), vec![&PathBuf::from("a.txt")]);
+    assert_eq!(index.search("rust".to_string()), vec![&PathBuf::from("a.txt")]);
+    assert_eq!(index.dictionary.doc_freq("title:rust"), 1);
+    index.delete_document(0).unwrap();
+    assert_eq!(index.dictionary.doc_freq("content:fast"), 0);
+    drop(index);
+    assert_eq!(InvertedIndex::load_docs_from_disk(&dir).unwrap().1, DOCS_VERSION);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+pub fn test_schema_reopen() { 
+    use schema::FieldEntry;
+
+    let dir = temp_index_dir("schema-reopen");
+    let schema = || Schema::new().field(FieldEntry::text(DEFAULT_FIELD, "english").stored()).field(FieldEntry::keyword("tag"));
+    let (a, b) = (PathBuf::from("a.txt"), PathBuf::from("b.txt"));
+    let mut index = InvertedIndex::open(&dir, Some(schema())).unwrap();
+    index.index_document(Document::new(&a).field(DEFAULT_FIELD, "rust").field("tag", "lang")).unwrap();
+    let before = (index.docs_count, index.dictionary.entries(), index.journal.read_records().len());
+    // a keyword may not hold a line break, and the whole document is refused
+    let err = index.index_document(Document::new(&b).field(DEFAULT_FIELD, "rust again").field("tag", "a\nb")).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert_eq!((index.docs_count, index.dictionary.entries(), index.journal.read_records().len()), before);
+    assert_eq!(index.docs.id_of(&b), None);
+    assert_eq!(index.search("rust".to_string()), vec![&a]);
+    index.sync().unwrap();
+    drop(index);
+
+    // what `with_schema` runs into in the working directory: a field whose
+    // type changed is refused and the saved schema is left alone
+    let changed = schema().field(FieldEntry::text("tag", "standard"));
+    let err = InvertedIndex::open(&dir, Some(changed)).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert!(err.to_string().contains("\"tag\""));
+    assert_eq!(Schema::load(&dir.join(SCHEMA_PATH)).unwrap(), schema());
+    // adding a field is fine
+    let mut index = InvertedIndex::open(&dir, Some(schema().field(FieldEntry::integer("year")))).unwrap();
+    assert_eq!(index.docs_count, 1);
+    assert_eq!(index.search("rust".to_string()), vec![&a]);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+pub fn test_highlight() { 
+    let dir = temp_index_dir("highlight");
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    let content = "Java is popular. Many teams pick Rust today because Rust is memory safe and fast, and systems code in Rust is pleasant.";
+    index.index_document(Document::new("a.txt").field(DEFAULT_FIELD, content)).unwrap();
+    let doc_id = index.docs.id_of(Path::new("a.txt")).unwrap();
+    index.sync().unwrap();
+    drop(index);
+
+    // the content comes back from the document store
+    let index = InvertedIndex::open(&dir, None).unwrap();
+    let highlighter = Highlighter::new("<b>", "</b>", 40, 2);
+    let highlight = |query: &str| index.highlight(&Query::parse(query).unwrap(), doc_id, &highlighter).unwrap();
+    assert_eq!(highlight("\"memory safe\""), vec!["…Rust is <b>memory</b> <b>safe</b> and fast, and…".to_string()]);
+    // every occurrence of the term the fuzzy query expands to, in two
+    // fragments cut at word boundaries, the last one running to the end
+    let fragments = highlight("rsut~1");
+    assert_eq!(fragments, vec![
+        "…pick <b>Rust</b> today because <b>Rust</b> is memory…".to_string(),
+        "…systems code in <b>Rust</b> is pleasant".to_string()
+    ]);
+    for fragment in fragments { 
+        let text = fragment.replace("<b>", "").replace("</b>", "").replace('…', "");
+        assert!(text.chars().count() <= 40, "{fragment}");
+        assert!(content.contains(&text));
+    }
+    assert!(highlight("java AND NOT rust").iter().all(|fragment| !fragment.contains("<b>Rust</b>")));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+pub fn test_similarity_ranking() { 
+    use scoring::{ConstantScore, TfIdf};
+
+    let dir = temp_index_dir("similarity");
+    let mut index = InvertedIndex::open(&dir, None).unwrap();
+    let long = format!("{} {}", "rust ".repeat(8), "word ".repeat(12));
+    for (path, content) in [("a.txt", "rust go"), ("b.txt", long.as_str()), ("c.txt", "go python java"), ("d.txt", "java")] { 
+        index.add_document(content.to_string(), PathBuf::from(path)).unwrap();
+    }
+    let mut ranking = |query: &str, similarity: &dyn Similarity| -> Vec<(String, f32)> { 
+        index.search_ranked_with(&Query::parse(query).unwrap(), similarity).into_iter()
+            .map(|(path, score)| (path.to_string_lossy().into_owned(), score))
+            .collect()
+    };
+    let paths = |ranked: &[(String, f32)]| ranked.iter().map(|(path, _)| path.clone()).collect::<Vec<String>>();
+    // BM25 saturates the many occurrences in the long document more slowly
+    // than TF-IDF shortens its score for the length
+    assert_eq!(paths(&ranking("rust", &Bm25::default())), vec!["b.txt", "a.txt"]);
+    assert_eq!(paths(&ranking("rust", &TfIdf)), vec!["a.txt", "b.txt"]);
+    // a constant score only counts matching terms, ties go by id
+    let constant = ranking("rust", &ConstantScore::default());
+    assert_eq!(paths(&constant), vec!["a.txt", "b.txt"]);
+    assert!(constant.iter().all(|(_, score)| *score == 1.0));
+    assert_eq!(ranking("rust OR go", &ConstantScore(0.5)), vec![
+        ("a.txt".to_string(), 1.0), ("b.txt".to_string(), 0.5), ("c.txt".to_string(), 0.5)
+    ]);
+    // the index similarity stays BM25
+    assert_eq!(index.search_ranked(&Query::parse("rust").unwrap())[0].0, &PathBuf::from("b.txt"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
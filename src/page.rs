@@ -1,7 +1,6 @@
-use std::{collections::HashMap, fs::{self, File}, io::{self, Bytes, ErrorKind, Read, SeekFrom, Write}, os::unix::fs::FileExt, path::PathBuf};
+use std::{collections::HashMap, fs::{self, File}, io::{self, ErrorKind, Read, SeekFrom, Write}};
 use std::io::Seek;
 
-use std::io::Error;
 
 #[derive(Debug, Clone)]
 pub struct Page { 
@@ -41,10 +40,14 @@ impl Page {
         let offset = self.last_written_offset;
 
         println!("first offset : {offset}");
-        self.data[offset..offset+data.len()].copy_from_slice(&data[..]);
+        self.data[offset..offset+data.len()].copy_from_slice(data);
         self.last_written_offset = offset + data.len();
         self.is_dirty = true;
-        return offset;
+        offset
+    }
+
+    pub fn id(&self) -> usize { 
+        self.page_id
     }
 
     pub fn read(&self, offset: usize, size: usize) -> &[u8] { 
@@ -68,9 +71,9 @@ impl PageCacheManager {
     pub fn new(path: &std::path::Path, page_size: usize, cap: usize, last_page_offset_and_size: (usize, usize)) -> std::io::Result<Self>{ 
         let next_page_id = last_page_offset_and_size.0 / page_size + 1;
         match fs::OpenOptions::new()
-            .read(true).write(true).create(true).open(path) { 
+            .read(true).write(true).create(true).truncate(false).open(path) { 
             Ok(file) => { 
-                return Ok(Self { 
+                Ok(Self { 
                     pages: HashMap::new(),
                     cap,
                     page_size,
@@ -78,97 +81,61 @@ impl PageCacheManager {
                     file,
                     next_page_id,
                     last_page_offset_and_size
-                });
+                })
             },
             Err(err) => { 
-                return Err(err)
+                Err(err)
             }
         }
         
     }
 
+    /// Appends `data` to the last page, or to a new one when it does not
+    /// fit, and writes it through to the file. Page `id` holds the bytes of
+    /// the file from `id * page_size`, so the offset given back is both where
+    /// the data sits in the file and where `read` finds it.
     pub fn write(&mut self, data: &[u8]) -> Option<(usize, usize, usize)>{ 
-        // check if  the last page are full ;
+        if data.len() > self.page_size { 
+            return None
+        }
         self.usage_counter += 1;
-        let page_size = self.page_size;
         let last_used = self.usage_counter;
-        let offset = self.last_page_offset_and_size.0;
-        let size = self.last_page_offset_and_size.1;
-        let within_page_offset = offset % self.page_size;
-        let page = Page::open(self.next_page_id - 1, &vec![0u8; self.page_size], false, last_used, within_page_offset + size);
-        if !self.pages.contains_key(&(self.next_page_id - 1)) { 
-            self.pages.insert(self.next_page_id -1, page);
-        }
-        
-        if let Some((last_page_id, writable_id, page_written_offset, page_last_written_offset, size)) = if let Some(last_page_id) = self.pages.keys().max().copied() { 
-            // check if the last page is full else write to the last page 
-            println!("last page id: {last_page_id}");
-            if let Ok(page) = self.get_page(last_page_id) { 
-                println!("found the page");
-                let available_space = page_size - page.last_written_offset;
-                println!("available space :{available_space}");
-                if available_space >= data.len() { 
-                    let page_written_offset = page.write(data);
-                    page.last_used = last_used;
-                    // match self.flush(last_page_id) { 
-                    //     Ok(_) => { 
-                    //         page.is_dirty = false;
-                    //     },
-                    //     Err(err) => { 
-                    //         println!("error flushing  :{err:?}");
-                    //     }
-                    // }
-                    let writable_id = if last_page_id != 0 { 
-                        last_page_id -1
-                    } else { last_page_id};
-                    page.is_dirty = true;
-                    Some((last_page_id,writable_id, page_written_offset, page.last_written_offset, data.len()))
-                     
-                     
-                    
-                }  else { None}
-            } else { None}
-        }  else { None} { 
-            let _ = self.flush(last_page_id, page_written_offset, writable_id);
-            return Some((page_written_offset as usize + ( last_page_id * 4096),
-                                    (writable_id * 4096) + page_last_written_offset,
-                                    data.len()
-                                    ))
-        }
-        if self.pages.len() >= self.cap { 
-            println!("evicting");
-            self.evict();
-        }
-        let page_id = self.next_page_id;
-        println!("page id: {page_id}");
-        self.next_page_id += 1;
-        let mut page = Page::new(page_id, true, self.usage_counter);
-        let new_page_last_written_offset = page.write(data);
-        let writable_id = if page_id != 0 { 
-            page_id -1
-        } else { page_id};
-        match self.flush(page_id, new_page_last_written_offset, writable_id) { 
-            Ok(_) => { 
-                println!("flushed the page");
-                page.is_dirty = false;
-            },
-            Err(err) => { 
-                println!("error flushing  :{err:?}");
+        let page_size = self.page_size;
+        let (offset, size) = self.last_page_offset_and_size;
+        let mut page_id = self.next_page_id - 1;
+        // the end of the last record is where writing goes on, a page read
+        // back in only knows about the records that were read
+        let end = if offset / page_size == page_id && (offset, size) != (0, 0) { offset % page_size + size } else { 0 };
+        self.pages.entry(page_id)
+            .or_insert_with(|| Page::open(page_id, &vec![0u8; page_size], false, last_used, end))
+            .last_written_offset = end;
+        if page_size - end < data.len() { 
+            if self.pages.len() >= self.cap { 
+                let _ = self.evict();
             }
+            page_id = self.next_page_id;
+            self.next_page_id += 1;
+            self.pages.insert(page_id, Page::open(page_id, &vec![0u8; page_size], false, last_used, 0));
         }
-        
-        self.pages.insert(page_id, page.clone());
-        Some((offset as usize + ( page_id * self.page_size),
-        (writable_id * self.page_size) + page.last_written_offset, data.len()))
+        let page = self.pages.get_mut(&page_id)?;
+        page.last_used = last_used;
+        let page_offset = page.write(data);
+        if let Err(err) = self.flush(page_id, page_offset) { 
+            println!("error flushing  :{err:?}");
+            return None
+        }
+        let file_offset = page_id * page_size + page_offset;
+        Some((file_offset, file_offset, data.len()))
     }
+
     pub fn update_last_page_offset(&mut self, offset: usize, size: usize) { 
         self.last_page_offset_and_size = (offset, size);
     }
 
     pub fn read(&mut self, offset: usize, size: usize) -> io::Result<&[u8]> { 
         println!("offset and size are {}, {}", offset, size);
-        let page_id = (offset/ self.page_size);
-        let within_page_offset = (offset as usize % self.page_size) as usize;
+        let page_id = offset/ self.page_size ;
+        let within_page_offset = offset % self.page_size ;
         
         if !self.pages.contains_key(&page_id){ 
             println!("page does not contain a key");
@@ -181,6 +148,9 @@ impl PageCacheManager {
             data[within_page_offset..within_page_offset+size].copy_from_slice(&buf);
             let last_written_offset = Self::find_last_written_offset(&data);
             let page = Page::open(page_id, &data[..], false, self.usage_counter, last_written_offset);
+            if self.pages.len() >= self.cap { 
+                let _ = self.evict();
+            }
             let _ = self.pages.insert(page_id, page);
             //println!("pages are : {:?}", self.pages);
         }
@@ -197,7 +167,7 @@ impl PageCacheManager {
             // Scope file read
             {
                 self.file.seek(SeekFrom::Start(offset as u64))?;
-                self.file.read(&mut buf)?;
+                let _ = self.file.read(&mut buf)?;
                 println!("buf are : {buf:?}");
             }
     
@@ -220,35 +190,31 @@ impl PageCacheManager {
     }
 
     pub fn evict(&mut self) -> std::io::Result<()> { 
-        if self.pages.len() >= self.cap { 
-            
-            if let Some(id) = self.pages.iter().min_by_key(|(_, page)| page.last_used)
-            .map(|(&id,_)| id) { 
-                // flush the page if dirty and next remove from cache 
-                //self.flush(id);
-                self.pages.remove(&id);
-            }
+        if self.pages.len() >= self.cap
+            && let Some(id) = self.pages.iter().min_by_key(|(_, page)| page.last_used)
+            .map(|(&id,_)| id) {
+            // flush the page if dirty and next remove from cache 
+            //self.flush(id);
+            self.pages.remove(&id);
         }
         Ok(())
     }
 
-    pub fn flush(&mut self, id: usize, offset: usize, writable_id: usize) -> std::io::Result<()>{ 
-        println!("flushing bytes");
-        
-        if let Some(page) = self.pages.get(&id) { 
-            if page.is_dirty { 
-                let begin_offset = (writable_id * self.page_size) + offset;
-                let end_offset = (writable_id* self.page_size) +  page.last_written_offset;
-                self.file.seek(SeekFrom::Start(begin_offset as u64));
-                self.file.write_all(&page.data[begin_offset..end_offset])?;
-                self.file.flush();
-            } 
+    /// Writes the bytes of page `id` from `offset` within the page up to
+    /// where it was last written to their place in the file.
+    pub fn flush(&mut self, id: usize, offset: usize) -> std::io::Result<()>{ 
+        if let Some(page) = self.pages.get_mut(&id)
+            && page.is_dirty {
+            self.file.seek(SeekFrom::Start((id * self.page_size + offset) as u64))?;
+            self.file.write_all(&page.data[offset..page.last_written_offset])?;
+            self.file.flush()?;
+            page.is_dirty = false;
         }
         Ok(())
     }
 
     pub fn flush_all(&mut self) -> io::Result<()>{ 
-        for id in self.pages.keys().copied().collect::<Vec<_>>() { 
+        for _id in self.pages.keys().copied().collect::<Vec<_>>() { 
             //self.flush(id);
         }
         Ok(())
@@ -262,12 +228,12 @@ impl PageCacheManager {
             match self.file.seek(SeekFrom::Start(offset)) { 
                 Ok(_) => { 
                     match self.file.read(&mut buf) { 
-                        Ok(_) => { 
+                        Ok(_read) => { 
                             let last_written_offset = Self::find_last_written_offset(&buf);
                             println!("last written offset: {last_written_offset}");
                             let page = Page::open(id, &buf, false, self.usage_counter, last_written_offset);
                             if self.pages.len() >= self.cap { 
-                                self.evict();
+                                let _ = self.evict();
                             }
                             self.pages.insert(id, page);
                             return Ok(self.pages.get_mut(&id).unwrap());
@@ -306,7 +272,7 @@ impl PageCacheManager {
             .rposition(|&b| b != 0)
             .map(|pos| pos + 1) // +1 because offset is exclusive
             .unwrap_or(0);
-        return total_written >= thresold
+        total_written >= thresold
     }
 
     pub fn mark_dirty(&mut self, id: usize) -> std::io::Result<()> { 
@@ -0,0 +1,349 @@
+//! Query string parser.
+//!
+//! ```text
+//! query     := or_expr EOF
+//! or_expr   := and_expr ( "OR" and_expr )*
+//! and_expr  := clause ( "AND"? clause )*
+//! clause    := ( "+" | "-" | "NOT" )? ( FIELD ":" )? atom ( "^" NUMBER )?
+//! atom      := TERM | PHRASE | "(" or_expr ")"
+//! PHRASE    := '"' words '"' ( "~" DIGITS )?
+//! TERM      := word                     exact term
+//!            | word "*"                 prefix
+//!            | word with "*" or "?"     wildcard
+//!            | word "~" DIGITS?         fuzzy, two edits when no distance is given
+//! FIELD     := [A-Za-z_][A-Za-z0-9_]*
+//! ```
+//!
+//! Clauses next to each other are all required, as if joined by `AND`, and
+//! `AND` binds tighter than `OR`. Once any clause of a run is marked with `+`
+//! or `-`, the unmarked clauses of that run become optional: they only add to
+//! the score, unless there is no required clause, in which case at least one
+//! of them has to match. `-` and `NOT` exclude a clause in either case.
+//!
+//! Errors carry the byte offset into the input where parsing went wrong.
+
+use super::query::{Query, QueryError};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Phrase(Vec<String>, usize),
+    Field(String),
+    Boost(f32),
+    Plus,
+    Minus,
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen
+}
+
+fn error(position: usize, message: impl Into<String>) -> QueryError {
+    QueryError { position, message: message.into() }
+}
+
+fn ends_word(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '(' | ')' | '"' | '^')
+}
+
+fn is_field_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Reads the digits (and, for boosts, a decimal point) starting at `start`.
+fn number_at(input: &str, start: usize, decimal: bool) -> &str {
+    let len = input[start..].find(|c: char| !(c.is_ascii_digit() || (decimal && c == '.'))).unwrap_or(input.len() - start);
+    &input[start..start + len]
+}
+
+fn lex(input: &str) -> Result<Vec<(Token, usize)>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while let Some(c) = input[pos..].chars().next() {
+        let start = pos;
+        match c {
+            c if c.is_whitespace() => pos += c.len_utf8(),
+            '(' => {
+                tokens.push((Token::LParen, start));
+                pos += 1;
+            },
+            ')' => {
+                tokens.push((Token::RParen, start));
+                pos += 1;
+            },
+            '^' => {
+                let digits = number_at(input, start + 1, true);
+                let boost: f32 = digits.parse().map_err(|_| error(start + 1, "expected a number after '^'"))?;
+                tokens.push((Token::Boost(boost), start));
+                pos = start + 1 + digits.len();
+            },
+            '"' => {
+                let Some(len) = input[start + 1..].find('"') else {
+                    return Err(error(start, "unterminated phrase"))
+                };
+                let end = start + 1 + len;
+                let words = input[start + 1..end].split_whitespace().map(str::to_string).collect();
+                pos = end + 1;
+                let mut slop = 0;
+                if input[pos..].starts_with('~') {
+                    let digits = number_at(input, pos + 1, false);
+                    slop = digits.parse().map_err(|_| error(pos + 1, "expected a number after '~'"))?;
+                    pos += 1 + digits.len();
+                }
+                tokens.push((Token::Phrase(words, slop), start));
+            },
+            '+' | '-' if input[start + 1..].chars().next().is_some_and(|next| !next.is_whitespace()) => {
+                tokens.push((if c == '+' { Token::Plus } else { Token::Minus }, start));
+                pos += 1;
+            },
+            _ => {
+                let len = input[start..].find(ends_word).unwrap_or(input.len() - start);
+                let mut word = &input[start..start + len];
+                pos = start + len;
+                if let Some((name, rest)) = word.split_once(':')
+                    && is_field_name(name) {
+                    tokens.push((Token::Field(name.to_string()), start));
+                    if rest.is_empty() {
+                        continue;
+                    }
+                    word = rest;
+                }
+                let token = match word {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    word => Token::Word(word.to_string())
+                };
+                tokens.push((token, pos - word.len()));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Occur {
+    Must,
+    MustNot,
+    // no marker: required unless the run uses `+`/`-`, then optional
+    Default
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    input: &'a str
+}
+
+/// Parses a query string following the grammar in the module documentation.
+pub fn parse(input: &str) -> Result<Query, QueryError> {
+    let tokens = lex(input)?;
+    let mut parser = Parser { tokens, pos: 0, input };
+    let query = parser.parse_or()?;
+    if let Some((token, position)) = parser.tokens.get(parser.pos) {
+        return Err(error(*position, format!("unexpected {}", describe(token))))
+    }
+    Ok(query)
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(word) => format!("'{word}'"),
+        Token::Phrase(..) => "phrase".to_string(),
+        Token::Field(field) => format!("field '{field}:'"),
+        Token::Boost(_) => "'^'".to_string(),
+        Token::Plus => "'+'".to_string(),
+        Token::Minus => "'-'".to_string(),
+        Token::And => "AND".to_string(),
+        Token::Or => "OR".to_string(),
+        Token::Not => "NOT".to_string(),
+        Token::LParen => "'('".to_string(),
+        Token::RParen => "')'".to_string()
+    }
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map(|(_, position)| *position).unwrap_or(self.input.len())
+    }
+
+    fn unexpected(&self) -> QueryError {
+        match self.peek() {
+            Some(token) => error(self.position(), format!("unexpected {}", describe(token))),
+            None => error(self.position(), "unexpected end of query")
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Query, QueryError> {
+        let mut clauses = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            clauses.push(self.parse_and()?);
+        }
+        Ok(if clauses.len() == 1 { clauses.remove(0) } else { Query::Or(clauses) })
+    }
+
+    fn parse_and(&mut self) -> Result<Query, QueryError> {
+        let mut clauses = vec![self.parse_clause()?];
+        let mut marked = clauses[0].2;
+        loop {
+            let joined = self.peek() == Some(&Token::And);
+            if joined {
+                self.pos += 1;
+            } else if matches!(self.peek(), None | Some(Token::Or) | Some(Token::RParen)) {
+                break;
+            }
+            let clause = self.parse_clause()?;
+            marked |= clause.2;
+            clauses.push(clause);
+            if joined {
+                // `a AND b` requires both sides even in a run using `+`/`-`
+                let len = clauses.len();
+                for (occur, _, _) in &mut clauses[len - 2..] {
+                    if *occur == Occur::Default {
+                        *occur = Occur::Must;
+                    }
+                }
+            }
+        }
+
+        let (mut must, mut should, mut must_not) = (Vec::new(), Vec::new(), Vec::new());
+        for (occur, query, _) in clauses {
+            match occur {
+                Occur::Must => must.push(query),
+                Occur::MustNot => must_not.push(query),
+                Occur::Default if marked => should.push(query),
+                Occur::Default => must.push(query)
+            }
+        }
+        if !should.is_empty() {
+            return Ok(Query::Bool { must, should, must_not })
+        }
+        if must.len() == 1 && must_not.is_empty() {
+            return Ok(must.remove(0))
+        }
+        must.extend(must_not.into_iter().map(Query::not));
+        Ok(if must.len() == 1 { must.remove(0) } else { Query::And(must) })
+    }
+
+    /// Parses one clause; the flag tells whether it was marked with `+` or `-`.
+    fn parse_clause(&mut self) -> Result<(Occur, Query, bool), QueryError> {
+        let (occur, marked) = match self.peek() {
+            Some(Token::Plus) => (Occur::Must, true),
+            Some(Token::Minus) => (Occur::MustNot, true),
+            Some(Token::Not) => (Occur::MustNot, false),
+            _ => (Occur::Default, false)
+        };
+        if occur != Occur::Default {
+            self.pos += 1;
+        }
+        let field = match self.peek() {
+            Some(Token::Field(field)) => {
+                let field = field.clone();
+                self.pos += 1;
+                Some(field)
+            },
+            _ => None
+        };
+        let mut query = self.parse_atom()?;
+        if let Some(field) = field {
+            query = Query::Field { field, query: Box::new(query) };
+        }
+        if let Some(&Token::Boost(boost)) = self.peek() {
+            self.pos += 1;
+            query = Query::Boost { query: Box::new(query), boost };
+        }
+        Ok((occur, query, marked))
+    }
+
+    fn parse_atom(&mut self) -> Result<Query, QueryError> {
+        let position = self.position();
+        match self.peek().cloned() {
+            Some(Token::Word(word)) => {
+                self.pos += 1;
+                parse_term(word, position)
+            },
+            Some(Token::Phrase(terms, slop)) => {
+                self.pos += 1;
+                let positions = (0..terms.len()).collect();
+                Ok(Query::Phrase { terms, positions, slop })
+            },
+            Some(Token::LParen) => {
+                self.pos += 1;
+                let query = self.parse_or()?;
+                if self.peek() != Some(&Token::RParen) {
+                    return Err(match self.peek() {
+                        Some(_) => self.unexpected(),
+                        None => error(self.position(), "expected ')'")
+                    })
+                }
+                self.pos += 1;
+                Ok(query)
+            },
+            _ => Err(self.unexpected())
+        }
+    }
+}
+
+fn parse_term(word: String, position: usize) -> Result<Query, QueryError> {
+    if let Some((term, distance)) = word.rsplit_once('~').filter(|(term, _)| !term.is_empty()) {
+        let distance = if distance.is_empty() { 2 } else {
+            distance.parse().map_err(|_| error(position + term.len() + 1, "expected a number after '~'"))?
+        };
+        return Ok(Query::fuzzy(term, distance))
+    }
+    let wildcards = word.matches(['*', '?']).count();
+    if wildcards == 0 {
+        Ok(Query::Term(word))
+    } else if wildcards == 1 && word.len() > 1 && word.ends_with('*') {
+        Ok(Query::Prefix(word[..word.len() - 1].to_string()))
+    } else {
+        Ok(Query::Wildcard(word))
+    }
+}
+
+#[test]
+pub fn test_parse() {
+    let term = Query::term;
+    assert_eq!(parse("rust AND (systems OR language) NOT java").unwrap(), Query::and(vec![
+        term("rust"),
+        Query::or(vec![term("systems"), term("language")]),
+        Query::not(term("java"))
+    ]));
+    assert_eq!(parse("\"memory safe\" OR \"safe code\"~3").unwrap(), Query::or(vec![
+        Query::phrase(&["memory", "safe"], 0),
+        Query::phrase(&["safe", "code"], 3)
+    ]));
+    assert_eq!(parse("optim* te?t rust~1 rust~").unwrap(), Query::and(vec![
+        Query::Prefix("optim".to_string()),
+        Query::Wildcard("te?t".to_string()),
+        Query::fuzzy("rust", 1),
+        Query::fuzzy("rust", 2)
+    ]));
+    assert_eq!(parse("+rust systems -java").unwrap(), Query::Bool {
+        must: vec![term("rust")],
+        should: vec![term("systems")],
+        must_not: vec![term("java")]
+    });
+    assert_eq!(parse("title:rust^2 body:(memory OR \"safe code\")").unwrap(), Query::and(vec![
+        Query::Boost { query: Box::new(Query::Field { field: "title".to_string(), query: Box::new(term("rust")) }), boost: 2.0 },
+        Query::Field { field: "body".to_string(), query: Box::new(Query::or(vec![term("memory"), Query::phrase(&["safe", "code"], 0)])) }
+    ]));
+    assert_eq!(parse("memory-safe c++").unwrap(), Query::and(vec![term("memory-safe"), term("c++")]));
+
+    let position = |input| parse(input).unwrap_err().position;
+    assert_eq!(position("rust ("), 6);
+    assert_eq!(position("rust \"memory"), 5);
+    assert_eq!(position("rust~x"), 5);
+    assert_eq!(position("rust^x"), 5);
+    assert_eq!(position("(rust OR)"), 8);
+    assert_eq!(position("rust )"), 5);
+    assert_eq!(position("title:"), 6);
+}
@@ -0,0 +1,143 @@
+use std::io::{self, Error, ErrorKind};
+
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use super::DocumentId;
+
+/// Largest encoded chunk handed to the segment store in a single write.
+pub const MAX_CHUNK_SIZE: usize = 1024;
+
+/// Starts every chunk, followed by the format version as a byte. The bincode
+/// length a chunk used to start with is never this byte, so chunks without it
+/// are known to hold bare document ids, from before postings had positions.
+const CHUNK_TAG: u8 = 0xFF;
+const CHUNK_VERSION: u8 = 1;
+
+/// One document's occurrences of a term, positions counted in tokens.
+#[derive(Serialize, Deserialize, Encode, Decode, Clone, Debug, PartialEq)]
+pub struct Posting {
+    pub doc_id: DocumentId,
+    pub positions: Vec<usize>
+}
+
+impl Posting {
+    pub fn new(doc_id: DocumentId, positions: Vec<usize>) -> Self {
+        Self { doc_id, positions }
+    }
+}
+
+/// Encodes postings into chunks no bigger than `MAX_CHUNK_SIZE`, splitting the
+/// positions of a single document across chunks when they do not fit.
+pub fn encode_chunks(postings: &[Posting]) -> Vec<Vec<u8>> {
+    let mut chunks = Vec::new();
+    let mut pending: Vec<Posting> = Vec::new();
+    for posting in postings {
+        for part in posting.positions.chunks(MAX_CHUNK_SIZE / 8).map(|positions| Posting::new(posting.doc_id, positions.to_vec())) {
+            pending.push(part);
+            let Some(bytes) = encode_chunk(&pending) else { continue };
+            if bytes.len() > MAX_CHUNK_SIZE && pending.len() > 1 {
+                let last = pending.pop().unwrap();
+                chunks.extend(encode_chunk(&pending));
+                pending = vec![last];
+            }
+        }
+    }
+    if !pending.is_empty() {
+        chunks.extend(encode_chunk(&pending));
+    }
+    chunks
+}
+
+fn encode_chunk(postings: &[Posting]) -> Option<Vec<u8>> {
+    let mut chunk = vec![CHUNK_TAG, CHUNK_VERSION];
+    bincode::encode_into_std_write(postings, &mut chunk, bincode::config::standard()).ok()?;
+    Some(chunk)
+}
+
+/// Decodes a chunk written by `encode_chunks`. Chunks from before positions
+/// were kept come back with none, so they match terms but never phrases.
+pub fn decode_chunk(chunk: &[u8]) -> io::Result<Vec<Posting>> {
+    let config = bincode::config::standard();
+    let invalid = |err| Error::new(ErrorKind::InvalidData, format!("can not decode postings: {err}"));
+    match chunk {
+        [CHUNK_TAG, CHUNK_VERSION, postings @ ..] => bincode::decode_from_slice(postings, config).map(|(postings, _)| postings).map_err(invalid),
+        [CHUNK_TAG, version, ..] => Err(Error::new(ErrorKind::InvalidData, format!("unsupported postings version {version}"))),
+        legacy => bincode::decode_from_slice::<Vec<DocumentId>, _>(legacy, config)
+            .map(|(doc_ids, _)| doc_ids.into_iter().map(|doc_id| Posting::new(doc_id, Vec::new())).collect())
+            .map_err(invalid)
+    }
+}
+
+/// Sorts postings by document and folds the parts of a split document back together.
+pub fn merge(mut postings: Vec<Posting>) -> Vec<Posting> {
+    postings.sort_by_key(|posting| posting.doc_id);
+    let mut merged: Vec<Posting> = Vec::with_capacity(postings.len());
+    for posting in postings {
+        match merged.last_mut() {
+            Some(last) if last.doc_id == posting.doc_id => last.positions.extend(posting.positions),
+            _ => merged.push(posting)
+        }
+    }
+    for posting in merged.iter_mut() {
+        posting.positions.sort_unstable();
+        posting.positions.dedup();
+    }
+    merged
+}
+
+/// Checks whether the terms, given by their positions in one document, occur
+/// as a phrase. Term `i` is expected at `start + offsets[i]`; `slop` is how
+/// far apart the terms may drift from that, in total, and still count as a match.
+pub fn phrase_matches(positions: &[&[usize]], offsets: &[usize], slop: usize) -> bool {
+    if positions.is_empty() || positions.iter().any(|p| p.is_empty()) {
+        return false
+    }
+    // shift every term back to where the phrase would have started
+    let starts: Vec<Vec<isize>> = positions.iter().zip(offsets)
+        .map(|(p, &offset)| p.iter().map(|&pos| pos as isize - offset as isize).collect())
+        .collect();
+    // smallest window holding one start from every term
+    let mut cursors = vec![0usize; starts.len()];
+    loop {
+        let mut min = (0, isize::MAX);
+        let mut max = isize::MIN;
+        for (i, list) in starts.iter().enumerate() {
+            let value = list[cursors[i]];
+            if value < min.1 {
+                min = (i, value);
+            }
+            max = max.max(value);
+        }
+        if (max - min.1) as usize <= slop {
+            return true
+        }
+        cursors[min.0] += 1;
+        if cursors[min.0] >= starts[min.0].len() {
+            return false
+        }
+    }
+}
+
+#[test]
+pub fn test_postings() {
+    let positions: Vec<usize> = (0..1000).collect();
+    let chunks = encode_chunks(&[Posting::new(1, positions.clone()), Posting::new(2, vec![3, 9])]);
+    assert!(chunks.len() > 1 && chunks.iter().all(|chunk| chunk.len() <= MAX_CHUNK_SIZE));
+    let decoded: Vec<Posting> = chunks.iter().flat_map(|chunk| decode_chunk(chunk).unwrap()).collect();
+    assert_eq!(merge(decoded), vec![Posting::new(1, positions), Posting::new(2, vec![3, 9])]);
+
+    // chunks from before positions hold bare document ids
+    let legacy = bincode::encode_to_vec(vec![4usize, 7], bincode::config::standard()).unwrap();
+    assert_eq!(decode_chunk(&legacy).unwrap(), vec![Posting::new(4, vec![]), Posting::new(7, vec![])]);
+    assert_eq!(decode_chunk(&[CHUNK_TAG, 9, 0]).unwrap_err().kind(), ErrorKind::InvalidData);
+    assert_eq!(decode_chunk(&[3, 1]).unwrap_err().kind(), ErrorKind::InvalidData);
+
+    // "memory safe" at 4..5, "safe memory" at 10..11
+    assert!(phrase_matches(&[&[4, 10], &[5, 11]], &[0, 1], 0));
+    assert!(!phrase_matches(&[&[4], &[7]], &[0, 1], 0));
+    assert!(phrase_matches(&[&[4], &[7]], &[0, 1], 2));
+    assert!(!phrase_matches(&[&[4], &[8]], &[0, 1], 2));
+    // "state of the art" with the stop words gone
+    assert!(phrase_matches(&[&[7], &[10]], &[0, 3], 0));
+}
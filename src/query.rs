@@ -0,0 +1,346 @@
+use std::fmt;
+
+use super::DocumentId;
+use super::parser;
+use super::posting::{self, Posting};
+
+/// A boolean query over the terms of the inverted index.
+///
+/// `Not` only makes sense relative to something else: inside an `And` it
+/// subtracts from the other clauses, anywhere else it is taken against the
+/// set of all indexed documents. `Phrase` matches its terms at their
+/// `positions` relative to each other, or within `slop` positions for
+/// proximity queries; terms sharing a position are alternatives.
+/// `Prefix` and `Wildcard` (`*` for any run of characters, `?` for exactly
+/// one) expand to every matching term of the dictionary, and `Fuzzy` to
+/// every term within `distance` edits.
+///
+/// `Bool` is what `+`/`-` clauses parse into: every `must` clause has to
+/// match, `should` clauses only add to the score unless there is no `must`,
+/// and `must_not` clauses exclude. `Field` restricts its query to one field
+/// and `Boost` scales the score of its query.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    Term(String),
+    Phrase { terms: Vec<String>, positions: Vec<usize>, slop: usize },
+    Prefix(String),
+    Wildcard(String),
+    Fuzzy { term: String, distance: usize, transpositions: bool },
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+    Bool { must: Vec<Query>, should: Vec<Query>, must_not: Vec<Query> },
+    Field { field: String, query: Box<Query> },
+    Boost { query: Box<Query>, boost: f32 }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryError {
+    pub position: usize,
+    pub message: String
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+impl Query {
+    pub fn term(term: &str) -> Self {
+        Query::Term(term.to_string())
+    }
+
+    /// A phrase of consecutive terms.
+    pub fn phrase(terms: &[&str], slop: usize) -> Self {
+        Query::Phrase { terms: terms.iter().map(|term| term.to_string()).collect(), positions: (0..terms.len()).collect(), slop }
+    }
+
+    pub fn fuzzy(term: &str, distance: usize) -> Self {
+        Query::Fuzzy { term: term.to_string(), distance, transpositions: true }
+    }
+
+    pub fn and(clauses: Vec<Query>) -> Self {
+        Query::And(clauses)
+    }
+
+    pub fn or(clauses: Vec<Query>) -> Self {
+        Query::Or(clauses)
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(query: Query) -> Self {
+        Query::Not(Box::new(query))
+    }
+
+    /// Parses a query string, see `parser` for the grammar.
+    pub fn parse(input: &str) -> Result<Query, QueryError> {
+        parser::parse(input)
+    }
+}
+
+/// Matches `text` against a pattern where `*` stands for any run of
+/// characters and `?` for exactly one.
+pub fn wildcard_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // where the last `*` was seen and how much text it has swallowed so far
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Intersects two sorted, deduplicated posting lists.
+pub fn intersect(left: &[DocumentId], right: &[DocumentId]) -> Vec<DocumentId> {
+    let mut result = Vec::with_capacity(left.len().min(right.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        match left[i].cmp(&right[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                result.push(left[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    result
+}
+
+/// Unions two sorted, deduplicated posting lists.
+pub fn union(left: &[DocumentId], right: &[DocumentId]) -> Vec<DocumentId> {
+    let mut result = Vec::with_capacity(left.len() + right.len());
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        match left[i].cmp(&right[j]) {
+            std::cmp::Ordering::Less => {
+                result.push(left[i]);
+                i += 1;
+            },
+            std::cmp::Ordering::Greater => {
+                result.push(right[j]);
+                j += 1;
+            },
+            std::cmp::Ordering::Equal => {
+                result.push(left[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    result.extend_from_slice(&left[i..]);
+    result.extend_from_slice(&right[j..]);
+    result
+}
+
+/// Removes every id of `right` from `left`; both lists must be sorted.
+pub fn difference(left: &[DocumentId], right: &[DocumentId]) -> Vec<DocumentId> {
+    let mut result = Vec::with_capacity(left.len());
+    let mut j = 0;
+    for &doc in left {
+        while j < right.len() && right[j] < doc {
+            j += 1;
+        }
+        if j < right.len() && right[j] == doc {
+            continue;
+        }
+        result.push(doc);
+    }
+    result
+}
+
+/// The documents matching a query, produced in increasing order one at a
+/// time. Only the posting lists of the query terms are held, never the list
+/// of matches, so a collector keeping the best few takes memory for those.
+#[derive(Debug)]
+pub enum DocSet {
+    /// The documents of a posting list, from `next` on.
+    Postings { postings: Vec<Posting>, next: usize },
+    /// The documents of a sorted list of ids, from `next` on.
+    Ids { ids: Vec<DocumentId>, next: usize },
+    /// Documents with the terms of `lists` at `offsets`, see
+    /// `posting::phrase_matches`; `cursors` are the next posting of each list.
+    Phrase { lists: Vec<Vec<Posting>>, offsets: Vec<usize>, slop: usize, cursors: Vec<usize> },
+    Union(Vec<Head>),
+    Intersection(Vec<Head>),
+    /// Documents of the first set missing from the second.
+    Difference(Box<DocSet>, Box<Head>)
+}
+
+/// A doc set along with the document it is on, for sets moved through
+/// together.
+#[derive(Debug)]
+pub struct Head {
+    set: DocSet,
+    doc: Option<DocumentId>
+}
+
+impl Head {
+    fn new(mut set: DocSet) -> Self {
+        let doc = set.next();
+        Self { set, doc }
+    }
+
+    fn advance(&mut self) {
+        self.doc = self.set.next();
+    }
+
+    /// Moves on to the first document at or after `target`, unless already there.
+    fn seek(&mut self, target: DocumentId) -> Option<DocumentId> {
+        if self.doc.is_some_and(|doc| doc < target) {
+            self.doc = self.set.seek(target);
+        }
+        self.doc
+    }
+}
+
+impl DocSet {
+    pub fn empty() -> Self {
+        DocSet::Ids { ids: Vec::new(), next: 0 }
+    }
+
+    pub fn postings(postings: Vec<Posting>) -> Self {
+        DocSet::Postings { postings, next: 0 }
+    }
+
+    pub fn ids(ids: Vec<DocumentId>) -> Self {
+        DocSet::Ids { ids, next: 0 }
+    }
+
+    /// `lists` holds the postings of the terms expected at each of `offsets`.
+    pub fn phrase(lists: Vec<Vec<Posting>>, offsets: Vec<usize>, slop: usize) -> Self {
+        let cursors = vec![0; lists.len()];
+        DocSet::Phrase { lists, offsets, slop, cursors }
+    }
+
+    pub fn union(sets: Vec<DocSet>) -> Self {
+        DocSet::Union(sets.into_iter().map(Head::new).collect())
+    }
+
+    pub fn intersection(sets: Vec<DocSet>) -> Self {
+        DocSet::Intersection(sets.into_iter().map(Head::new).collect())
+    }
+
+    pub fn difference(include: DocSet, exclude: DocSet) -> Self {
+        DocSet::Difference(Box::new(include), Box::new(Head::new(exclude)))
+    }
+
+    /// Skips to the first document at or after `target` and returns it.
+    pub fn seek(&mut self, target: DocumentId) -> Option<DocumentId> {
+        match self {
+            DocSet::Postings { postings, next } => *next += postings[*next..].partition_point(|posting| posting.doc_id < target),
+            DocSet::Ids { ids, next } => *next += ids[*next..].partition_point(|&doc| doc < target),
+            _ => {}
+        }
+        self.find(|&doc| doc >= target)
+    }
+}
+
+impl Iterator for DocSet {
+    type Item = DocumentId;
+
+    fn next(&mut self) -> Option<DocumentId> {
+        match self {
+            DocSet::Postings { postings, next } => {
+                let doc = postings.get(*next)?.doc_id;
+                *next += 1;
+                Some(doc)
+            },
+            DocSet::Ids { ids, next } => {
+                let doc = *ids.get(*next)?;
+                *next += 1;
+                Some(doc)
+            },
+            DocSet::Phrase { lists, offsets, slop, cursors } => {
+                let mut target = lists.first()?.get(cursors[0])?.doc_id;
+                loop {
+                    // every list is moved up to the furthest document until they agree
+                    let mut furthest = target;
+                    for (list, cursor) in lists.iter().zip(cursors.iter_mut()) {
+                        *cursor += list[*cursor..].partition_point(|posting| posting.doc_id < target);
+                        furthest = furthest.max(list.get(*cursor)?.doc_id);
+                    }
+                    if furthest > target {
+                        target = furthest;
+                        continue
+                    }
+                    let positions: Vec<&[usize]> = lists.iter().zip(cursors.iter()).map(|(list, &cursor)| list[cursor].positions.as_slice()).collect();
+                    let matched = posting::phrase_matches(&positions, offsets, *slop);
+                    cursors.iter_mut().for_each(|cursor| *cursor += 1);
+                    if matched {
+                        return Some(target)
+                    }
+                    target = lists[0].get(cursors[0])?.doc_id;
+                }
+            },
+            DocSet::Union(heads) => {
+                let doc = heads.iter().filter_map(|head| head.doc).min()?;
+                heads.iter_mut().filter(|head| head.doc == Some(doc)).for_each(Head::advance);
+                Some(doc)
+            },
+            DocSet::Intersection(heads) => {
+                let mut target = heads.first()?.doc?;
+                loop {
+                    let mut furthest = target;
+                    for head in heads.iter_mut() {
+                        furthest = furthest.max(head.seek(target)?);
+                    }
+                    if furthest == target {
+                        heads.iter_mut().for_each(Head::advance);
+                        return Some(target)
+                    }
+                    target = furthest;
+                }
+            },
+            DocSet::Difference(include, exclude) => include.find(|&doc| exclude.seek(doc) != Some(doc))
+        }
+    }
+}
+
+#[test]
+pub fn test_doc_set() {
+    let postings = |ids: &[DocumentId]| DocSet::postings(ids.iter().map(|&doc| Posting::new(doc, vec![0])).collect());
+    let union = DocSet::union(vec![postings(&[1, 4, 7]), postings(&[2, 4, 9]), DocSet::empty()]);
+    assert_eq!(union.collect::<Vec<_>>(), vec![1, 2, 4, 7, 9]);
+    let intersection = DocSet::intersection(vec![postings(&[1, 3, 4, 7, 9]), postings(&[3, 7, 9]), DocSet::ids(vec![0, 3, 5, 9])]);
+    assert_eq!(intersection.collect::<Vec<_>>(), vec![3, 9]);
+    let difference = DocSet::difference(DocSet::ids((0..8).collect()), postings(&[1, 2, 6, 10]));
+    assert_eq!(difference.collect::<Vec<_>>(), vec![0, 3, 4, 5, 7]);
+    let mut seeking = DocSet::union(vec![postings(&[1, 4, 7]), postings(&[2, 9])]);
+    assert_eq!(seeking.seek(3), Some(4));
+    assert_eq!(seeking.seek(4), Some(7));
+
+    // "a b": b right after a in 1 and 5, not in 3
+    let a = vec![Posting::new(1, vec![0]), Posting::new(3, vec![2]), Posting::new(5, vec![4, 8])];
+    let b = vec![Posting::new(1, vec![1]), Posting::new(2, vec![1]), Posting::new(3, vec![0]), Posting::new(5, vec![9])];
+    assert_eq!(DocSet::phrase(vec![a, b], vec![0, 1], 0).collect::<Vec<_>>(), vec![1, 5]);
+}
+
+#[test]
+pub fn test_posting_lists() {
+    assert_eq!(intersect(&[1, 3, 5, 7], &[3, 4, 7]), vec![3, 7]);
+    assert_eq!(union(&[1, 3, 5], &[2, 3, 6]), vec![1, 2, 3, 5, 6]);
+    assert_eq!(difference(&[1, 3, 5, 7], &[3, 7, 9]), vec![1, 5]);
+
+    assert!(wildcard_matches("te?t", "test") && !wildcard_matches("te?t", "tet"));
+    assert!(wildcard_matches("*mi*", "optimize") && wildcard_matches("o*e", "optimize"));
+    assert!(!wildcard_matches("o*z", "optimize"));
+}
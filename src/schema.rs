@@ -0,0 +1,241 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Error, ErrorKind, Read};
+use std::path::Path;
+
+use bincode::{Decode, Encode};
+
+use super::atomic_write;
+use super::document::Document;
+
+#[derive(Encode, Decode, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    /// Free text, split into terms by the field analyzer.
+    Text,
+    /// Indexed as a single term, exactly as given.
+    Keyword,
+    /// A signed 64-bit number, indexed as one term in canonical form.
+    Integer,
+    /// A calendar date written `YYYY-MM-DD`, indexed as one term.
+    Date,
+    /// Kept with the document but never indexed.
+    Stored
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FieldType::Text => "text",
+            FieldType::Keyword => "keyword",
+            FieldType::Integer => "integer",
+            FieldType::Date => "date",
+            FieldType::Stored => "stored"
+        };
+        f.write_str(name)
+    }
+}
+
+/// The declaration of one field.
+#[derive(Encode, Decode, Debug, Clone, PartialEq)]
+pub struct FieldEntry {
+    pub name: String,
+    pub field_type: FieldType,
+    /// Name of the analyzer in the index `AnalyzerRegistry`, text fields only.
+    pub analyzer: Option<String>,
+    pub indexed: bool,
+    pub stored: bool
+}
+
+impl FieldEntry {
+    fn new(name: &str, field_type: FieldType) -> Self {
+        Self { name: name.to_string(), field_type, analyzer: None, indexed: field_type != FieldType::Stored, stored: field_type == FieldType::Stored }
+    }
+
+    pub fn text(name: &str, analyzer: &str) -> Self {
+        Self { analyzer: Some(analyzer.to_string()), ..Self::new(name, FieldType::Text) }
+    }
+
+    pub fn keyword(name: &str) -> Self {
+        Self::new(name, FieldType::Keyword)
+    }
+
+    pub fn integer(name: &str) -> Self {
+        Self::new(name, FieldType::Integer)
+    }
+
+    pub fn date(name: &str) -> Self {
+        Self::new(name, FieldType::Date)
+    }
+
+    pub fn stored_only(name: &str) -> Self {
+        Self::new(name, FieldType::Stored)
+    }
+
+    /// Keeps the values with the document as well.
+    pub fn stored(mut self) -> Self {
+        self.stored = true;
+        self
+    }
+
+    /// Keeps the field out of the index; only useful together with `stored`.
+    pub fn unindexed(mut self) -> Self {
+        self.indexed = false;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaError {
+    UnknownField(String),
+    InvalidValue { field: String, value: String, expected: FieldType },
+    UnknownAnalyzer { field: String, analyzer: String },
+    /// The field was declared differently when the index was created.
+    Incompatible { field: String, reason: String }
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::UnknownField(field) => write!(f, "field {field:?} is not in the schema"),
+            SchemaError::InvalidValue { field, value, expected } => write!(f, "field {field:?} expects {expected} values, got {value:?}"),
+            SchemaError::UnknownAnalyzer { field, analyzer } => write!(f, "field {field:?} uses unknown analyzer {analyzer:?}"),
+            SchemaError::Incompatible { field, reason } => write!(f, "field {field:?} is incompatible with the index: {reason}")
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl From<SchemaError> for Error {
+    fn from(err: SchemaError) -> Self {
+        Error::new(ErrorKind::InvalidInput, err)
+    }
+}
+
+/// The fields documents of an index may have and how each is indexed.
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Default)]
+pub struct Schema {
+    fields: Vec<FieldEntry>
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares a field, replacing an earlier declaration of the same name.
+    pub fn field(mut self, entry: FieldEntry) -> Self {
+        self.fields.retain(|field| field.name != entry.name);
+        self.fields.push(entry);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&FieldEntry> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    pub fn fields(&self) -> &[FieldEntry] {
+        &self.fields
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut buf = Vec::new();
+        File::open(path)?.read_to_end(&mut buf)?;
+        bincode::decode_from_slice(&buf, bincode::config::standard())
+            .map(|(schema, _)| schema)
+            .map_err(|err| Error::new(ErrorKind::InvalidData, format!("can not decode schema: {err}")))
+    }
+
+    /// Writes the schema to `path`, see `atomic_write`.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let bytes = bincode::encode_to_vec(self, bincode::config::standard())
+            .map_err(|err| Error::new(ErrorKind::InvalidData, format!("can not encode schema: {err}")))?;
+        atomic_write(path, &bytes)
+    }
+
+    /// Checks that every field of `document` is declared and every value
+    /// parses as its field type.
+    pub fn validate(&self, document: &Document) -> Result<(), SchemaError> {
+        for (field, value) in &document.fields {
+            self.normalize(field, value)?;
+        }
+        Ok(())
+    }
+
+    /// The term a value of a keyword, integer or date field is indexed as, or
+    /// the value itself for text and stored fields. Keywords are taken whole,
+    /// so they may not hold control characters such as line breaks.
+    pub fn normalize(&self, field: &str, value: &str) -> Result<String, SchemaError> {
+        let entry = self.get(field).ok_or_else(|| SchemaError::UnknownField(field.to_string()))?;
+        let invalid = || SchemaError::InvalidValue { field: field.to_string(), value: value.to_string(), expected: entry.field_type };
+        match entry.field_type {
+            FieldType::Text | FieldType::Stored => Ok(value.to_string()),
+            FieldType::Keyword if value.trim().chars().any(char::is_control) => Err(invalid()),
+            FieldType::Keyword => Ok(value.trim().to_string()),
+            FieldType::Integer => value.trim().parse::<i64>().map(|number| number.to_string()).map_err(|_| invalid()),
+            FieldType::Date => parse_date(value.trim()).ok_or_else(invalid)
+        }
+    }
+
+    /// Checks that this schema can open an index created with `existing`:
+    /// fields may be added, but a field that exists must keep its type,
+    /// analyzer and whether it is indexed.
+    pub fn check_compatible(&self, existing: &Schema) -> Result<(), SchemaError> {
+        for old in &existing.fields {
+            let incompatible = |reason: String| SchemaError::Incompatible { field: old.name.clone(), reason };
+            let new = self.get(&old.name).ok_or_else(|| incompatible("it was removed".to_string()))?;
+            if new.field_type != old.field_type {
+                return Err(incompatible(format!("type changed from {} to {}", old.field_type, new.field_type)))
+            }
+            if new.analyzer != old.analyzer {
+                return Err(incompatible(format!("analyzer changed from {:?} to {:?}", old.analyzer, new.analyzer)))
+            }
+            if new.indexed != old.indexed {
+                return Err(incompatible("indexed changed".to_string()))
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Validates a `YYYY-MM-DD` date.
+fn parse_date(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '-');
+    let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return None
+    }
+    let (year, month, day): (u32, u32, u32) = (year.parse().ok()?, month.parse().ok()?, day.parse().ok()?);
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return None
+    };
+    (1..=days).contains(&day).then(|| value.to_string())
+}
+
+#[test]
+pub fn test_schema() {
+    let schema = Schema::new()
+        .field(FieldEntry::text("title", "english"))
+        .field(FieldEntry::keyword("tags"))
+        .field(FieldEntry::integer("year"))
+        .field(FieldEntry::date("published"))
+        .field(FieldEntry::stored_only("url"));
+    let document = Document::new("a").field("title", "Rust").field("year", "2017").field("published", "2024-02-29");
+    assert_eq!(schema.validate(&document), Ok(()));
+    assert_eq!(schema.normalize("year", " 007"), Ok("7".to_string()));
+    assert!(matches!(schema.validate(&document.clone().field("author", "Jim")), Err(SchemaError::UnknownField(_))));
+    assert!(schema.normalize("year", "soon").is_err());
+    assert!(schema.normalize("published", "2023-02-29").is_err());
+    assert_eq!(schema.normalize("tags", " rust \n"), Ok("rust".to_string()));
+    assert!(matches!(schema.normalize("tags", "rust\n#tombstone,0"), Err(SchemaError::InvalidValue { .. })));
+
+    let grown = schema.clone().field(FieldEntry::keyword("author"));
+    assert_eq!(grown.check_compatible(&schema), Ok(()));
+    assert!(schema.check_compatible(&grown).is_err());
+    assert!(schema.clone().field(FieldEntry::text("tags", "standard")).check_compatible(&schema).is_err());
+}
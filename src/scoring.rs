@@ -0,0 +1,107 @@
+use std::fmt::Debug;
+
+/// What a similarity gets to look at when scoring one term in one document.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TermStats {
+    pub term_freq: usize,
+    pub doc_len: usize,
+    pub avg_doc_len: f32,
+    pub doc_freq: usize,
+    pub docs_count: usize
+}
+
+/// A relevance function. The score of a document is the sum of the scores of
+/// the query terms it contains.
+pub trait Similarity: Debug {
+    fn score(&self, stats: &TermStats) -> f32;
+}
+
+/// Okapi BM25 relevance scoring.
+///
+/// `k1` controls how quickly repeated occurrences of a term saturate and `b`
+/// how strongly scores are normalised by document length.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bm25 {
+    pub k1: f32,
+    pub b: f32
+}
+
+impl Default for Bm25 {
+    fn default() -> Self {
+        Self { k1: 1.2, b: 0.75 }
+    }
+}
+
+impl Bm25 {
+    pub fn new(k1: f32, b: f32) -> Self {
+        Self { k1, b }
+    }
+
+    pub fn idf(&self, doc_freq: usize, docs_count: usize) -> f32 {
+        let doc_freq = doc_freq as f32;
+        (1.0 + (docs_count as f32 - doc_freq + 0.5) / (doc_freq + 0.5)).ln()
+    }
+}
+
+impl Similarity for Bm25 {
+    fn score(&self, stats: &TermStats) -> f32 {
+        if stats.term_freq == 0 {
+            return 0.0
+        }
+        let tf = stats.term_freq as f32;
+        let norm = if stats.avg_doc_len > 0.0 { stats.doc_len as f32 / stats.avg_doc_len } else { 1.0 };
+        self.idf(stats.doc_freq, stats.docs_count) * tf * (self.k1 + 1.0) / (tf + self.k1 * (1.0 - self.b + self.b * norm))
+    }
+}
+
+/// Classic TF-IDF: `sqrt(tf) * idf^2 / sqrt(doc_len)`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TfIdf;
+
+impl TfIdf {
+    pub fn idf(&self, doc_freq: usize, docs_count: usize) -> f32 {
+        1.0 + (docs_count as f32 / (doc_freq as f32 + 1.0)).ln()
+    }
+}
+
+impl Similarity for TfIdf {
+    fn score(&self, stats: &TermStats) -> f32 {
+        if stats.term_freq == 0 {
+            return 0.0
+        }
+        let idf = self.idf(stats.doc_freq, stats.docs_count);
+        let norm = 1.0 / (stats.doc_len.max(1) as f32).sqrt();
+        (stats.term_freq as f32).sqrt() * idf * idf * norm
+    }
+}
+
+/// Gives every matching term the same score, so documents rank by how many
+/// distinct query terms they contain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConstantScore(pub f32);
+
+impl Default for ConstantScore {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl Similarity for ConstantScore {
+    fn score(&self, stats: &TermStats) -> f32 {
+        if stats.term_freq == 0 { 0.0 } else { self.0 }
+    }
+}
+
+#[test]
+pub fn test_similarities() {
+    let stats = |term_freq, doc_len, doc_freq| TermStats { term_freq, doc_len, avg_doc_len: 10.0, doc_freq, docs_count: 100 };
+    let similarities: Vec<Box<dyn Similarity>> = vec![Box::new(Bm25::default()), Box::new(TfIdf)];
+    for similarity in similarities {
+        // rarer terms and more occurrences score higher, longer documents lower
+        assert!(similarity.score(&stats(1, 10, 1)) > similarity.score(&stats(1, 10, 50)));
+        assert!(similarity.score(&stats(3, 10, 5)) > similarity.score(&stats(1, 10, 5)));
+        assert!(similarity.score(&stats(1, 40, 5)) < similarity.score(&stats(1, 10, 5)));
+        assert_eq!(similarity.score(&stats(0, 10, 5)), 0.0);
+    }
+    assert_eq!(ConstantScore::default().score(&stats(7, 10, 5)), 1.0);
+}
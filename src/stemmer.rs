@@ -0,0 +1,259 @@
+//! Stemmers reducing inflected words to a common stem. English uses the
+//! Porter algorithm; French, German and Spanish use light stemmers that only
+//! strip plural, gender and the most frequent inflectional endings.
+
+use super::analysis::Language;
+
+/// Stems a lowercased word.
+pub fn stem(language: Language, word: &str) -> String {
+    match language {
+        Language::English => porter(word),
+        Language::French => french(word),
+        Language::German => german(word),
+        Language::Spanish => spanish(word)
+    }
+}
+
+fn is_consonant(w: &[u8], i: usize) -> bool {
+    match w[i] {
+        b'a' | b'e' | b'i' | b'o' | b'u' => false,
+        b'y' => i == 0 || !is_consonant(w, i - 1),
+        _ => true
+    }
+}
+
+/// Number of vowel-consonant sequences in `w`, Porter's `m`.
+fn measure(w: &[u8]) -> usize {
+    let mut n = 0;
+    let mut i = 0;
+    while i < w.len() && is_consonant(w, i) {
+        i += 1;
+    }
+    loop {
+        while i < w.len() && !is_consonant(w, i) {
+            i += 1;
+        }
+        if i >= w.len() {
+            return n
+        }
+        while i < w.len() && is_consonant(w, i) {
+            i += 1;
+        }
+        n += 1;
+    }
+}
+
+fn has_vowel(w: &[u8]) -> bool {
+    (0..w.len()).any(|i| !is_consonant(w, i))
+}
+
+fn ends_double_consonant(w: &[u8]) -> bool {
+    let len = w.len();
+    len >= 2 && w[len - 1] == w[len - 2] && is_consonant(w, len - 1)
+}
+
+/// Consonant-vowel-consonant, the last consonant not w, x or y.
+fn ends_cvc(w: &[u8]) -> bool {
+    let len = w.len();
+    len >= 3 && is_consonant(w, len - 3) && !is_consonant(w, len - 2) && is_consonant(w, len - 1)
+        && !matches!(w[len - 1], b'w' | b'x' | b'y')
+}
+
+/// Replaces the first suffix of `rules` that `w` ends with, provided the
+/// remaining stem has a measure above `min`. Returns whether a suffix matched,
+/// replaced or not.
+fn replace_suffix(w: &mut Vec<u8>, rules: &[(&str, &str)], min: usize) -> bool {
+    for (suffix, replacement) in rules {
+        if w.ends_with(suffix.as_bytes()) {
+            let stem = w.len() - suffix.len();
+            if measure(&w[..stem]) > min {
+                w.truncate(stem);
+                w.extend_from_slice(replacement.as_bytes());
+            }
+            return true
+        }
+    }
+    false
+}
+
+fn porter(word: &str) -> String {
+    if word.len() <= 2 || !word.bytes().all(|b| b.is_ascii_lowercase()) {
+        return word.to_string()
+    }
+    let mut w = word.as_bytes().to_vec();
+
+    // step 1a: plurals
+    if w.ends_with(b"sses") || w.ends_with(b"ies") {
+        w.truncate(w.len() - 2);
+    } else if !w.ends_with(b"ss") && w.ends_with(b"s") {
+        w.pop();
+    }
+
+    // step 1b: -ed and -ing
+    if w.ends_with(b"eed") {
+        if measure(&w[..w.len() - 3]) > 0 {
+            w.pop();
+        }
+    } else if let Some(suffix) = [&b"ed"[..], b"ing"].into_iter().find(|suffix| w.ends_with(suffix) && has_vowel(&w[..w.len() - suffix.len()])) {
+        w.truncate(w.len() - suffix.len());
+        if w.ends_with(b"at") || w.ends_with(b"bl") || w.ends_with(b"iz") {
+            w.push(b'e');
+        } else if ends_double_consonant(&w) && !matches!(w[w.len() - 1], b'l' | b's' | b'z') {
+            w.pop();
+        } else if measure(&w) == 1 && ends_cvc(&w) {
+            w.push(b'e');
+        }
+    }
+
+    // step 1c: terminal y
+    if w.ends_with(b"y") && has_vowel(&w[..w.len() - 1]) {
+        *w.last_mut().unwrap() = b'i';
+    }
+
+    // step 2: double suffixes
+    replace_suffix(&mut w, &[
+        ("ational", "ate"), ("tional", "tion"), ("enci", "ence"), ("anci", "ance"), ("izer", "ize"),
+        ("bli", "ble"), ("alli", "al"), ("entli", "ent"), ("eli", "e"), ("ousli", "ous"),
+        ("ization", "ize"), ("ation", "ate"), ("ator", "ate"), ("alism", "al"), ("iveness", "ive"),
+        ("fulness", "ful"), ("ousness", "ous"), ("aliti", "al"), ("iviti", "ive"), ("biliti", "ble"),
+        ("logi", "log")
+    ], 0);
+
+    // step 3: -ic-, -full, -ness
+    replace_suffix(&mut w, &[
+        ("icate", "ic"), ("ative", ""), ("alize", "al"), ("iciti", "ic"), ("ical", "ic"), ("ful", ""), ("ness", "")
+    ], 0);
+
+    // step 4: remaining suffixes on long stems, -ion only after s or t
+    let stem_before_ion = w.len().checked_sub(4).map(|i| matches!(w[i], b's' | b't'));
+    if w.ends_with(b"ion") {
+        if stem_before_ion == Some(true) && measure(&w[..w.len() - 3]) > 1 {
+            w.truncate(w.len() - 3);
+        }
+    } else {
+        replace_suffix(&mut w, &[
+            ("al", ""), ("ance", ""), ("ence", ""), ("er", ""), ("ic", ""), ("able", ""), ("ible", ""),
+            ("ant", ""), ("ement", ""), ("ment", ""), ("ent", ""), ("ou", ""), ("ism", ""), ("ate", ""),
+            ("iti", ""), ("ous", ""), ("ive", ""), ("ize", "")
+        ], 1);
+    }
+
+    // step 5: final e and ll
+    if w.ends_with(b"e") {
+        let m = measure(&w[..w.len() - 1]);
+        if m > 1 || (m == 1 && !ends_cvc(&w[..w.len() - 1])) {
+            w.pop();
+        }
+    }
+    if measure(&w) > 1 && ends_double_consonant(&w) && w.ends_with(b"l") {
+        w.pop();
+    }
+    String::from_utf8(w).unwrap_or_else(|_| word.to_string())
+}
+
+fn ends_with(w: &[char], suffix: &str) -> bool {
+    let suffix: Vec<char> = suffix.chars().collect();
+    w.ends_with(&suffix)
+}
+
+fn french(word: &str) -> String {
+    let mut w: Vec<char> = word.chars().collect();
+    if w.len() < 4 {
+        return word.to_string()
+    }
+    // plurals: chevaux -> cheval, maisons -> maison
+    if w.len() > 4 && ends_with(&w, "aux") {
+        w.truncate(w.len() - 2);
+        w.push('l');
+    } else if w.len() > 3 && matches!(w[w.len() - 1], 's' | 'x') {
+        w.pop();
+    }
+    // feminine and participles: grande -> grand, aimée -> aim, belle -> bel
+    if w.len() > 4 && w[w.len() - 1] == 'e' {
+        w.pop();
+    }
+    if w.len() > 3 && w[w.len() - 1] == 'é' {
+        w.pop();
+    }
+    if w.len() > 4 && w[w.len() - 1] == w[w.len() - 2] && !"aeiouy".contains(w[w.len() - 1]) {
+        w.pop();
+    }
+    w.into_iter().collect()
+}
+
+fn german(word: &str) -> String {
+    let mut w: Vec<char> = word.chars().flat_map(|c| match c {
+        'ä' | 'à' | 'á' | 'â' => vec!['a'],
+        'ö' | 'ò' | 'ó' | 'ô' => vec!['o'],
+        'ü' | 'ù' | 'ú' | 'û' => vec!['u'],
+        'ï' | 'ì' | 'í' | 'î' => vec!['i'],
+        'ß' => vec!['s', 's'],
+        c => vec![c]
+    }).collect();
+    let s_ending = |c: char| "bdfghklmnrt".contains(c);
+    let st_ending = |c: char| "bdfghklmnt".contains(c);
+
+    // step 1: -ern, -em, -en, -er, -es, -e, -s
+    let len = w.len();
+    if len > 5 && ends_with(&w, "ern") {
+        w.truncate(len - 3);
+    } else if len > 4 && (ends_with(&w, "em") || ends_with(&w, "en") || ends_with(&w, "er") || ends_with(&w, "es")) {
+        w.truncate(len - 2);
+    } else if len > 3 && (w[len - 1] == 'e' || (w[len - 1] == 's' && s_ending(w[len - 2]))) {
+        w.truncate(len - 1);
+    }
+
+    // step 2: -est, -er, -en, -st
+    let len = w.len();
+    if len > 5 && ends_with(&w, "est") {
+        w.truncate(len - 3);
+    } else if len > 4 && (ends_with(&w, "er") || ends_with(&w, "en") || (ends_with(&w, "st") && st_ending(w[len - 3]))) {
+        w.truncate(len - 2);
+    }
+    w.into_iter().collect()
+}
+
+fn spanish(word: &str) -> String {
+    let mut w: Vec<char> = word.chars().map(|c| match c {
+        'à' | 'á' | 'â' | 'ä' => 'a',
+        'ò' | 'ó' | 'ô' | 'ö' => 'o',
+        'è' | 'é' | 'ê' | 'ë' => 'e',
+        'ù' | 'ú' | 'û' | 'ü' => 'u',
+        'ì' | 'í' | 'î' | 'ï' => 'i',
+        c => c
+    }).collect();
+    let len = w.len();
+    if len < 5 {
+        return w.into_iter().collect()
+    }
+    match w[len - 1] {
+        'o' | 'a' | 'e' => w.truncate(len - 1),
+        // voces -> voz
+        's' if ends_with(&w, "ces") => {
+            w.truncate(len - 2);
+            w[len - 3] = 'z';
+        },
+        's' if ends_with(&w, "os") || ends_with(&w, "as") || ends_with(&w, "es") => w.truncate(len - 2),
+        _ => {}
+    }
+    w.into_iter().collect()
+}
+
+#[test]
+pub fn test_stemmers() {
+    let english = [
+        ("caresses", "caress"), ("ponies", "poni"), ("cats", "cat"), ("agreed", "agre"), ("hopping", "hop"),
+        ("running", "run"), ("runs", "run"), ("happy", "happi"), ("relational", "relat"), ("conditional", "condit"),
+        ("generalization", "gener"), ("hopeful", "hope"), ("adjustment", "adjust"), ("controlling", "control"),
+        ("languages", "languag"), ("language", "languag"), ("systems", "system"), ("is", "is")
+    ];
+    for (word, expected) in english {
+        assert_eq!(stem(Language::English, word), expected, "{word}");
+    }
+    assert_eq!(stem(Language::French, "chevaux"), "cheval");
+    assert_eq!(stem(Language::French, "grandes"), stem(Language::French, "grand"));
+    assert_eq!(stem(Language::German, "häuser"), stem(Language::German, "haus"));
+    assert_eq!(stem(Language::German, "kindern"), stem(Language::German, "kinder"));
+    assert_eq!(stem(Language::Spanish, "voces"), "voz");
+    assert_eq!(stem(Language::Spanish, "chicas"), stem(Language::Spanish, "chico"));
+}
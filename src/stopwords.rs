@@ -0,0 +1,40 @@
+//! Built-in stop-word lists: words too frequent to be worth indexing.
+
+use super::analysis::Language;
+
+pub const ENGLISH: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "if", "in", "into", "is", "it",
+    "no", "not", "of", "on", "or", "such", "that", "the", "their", "then", "there", "these",
+    "they", "this", "to", "was", "will", "with"
+];
+
+pub const FRENCH: &[&str] = &[
+    "au", "aux", "avec", "ce", "ces", "dans", "de", "des", "du", "elle", "en", "et", "eux", "il",
+    "je", "la", "le", "les", "leur", "lui", "ma", "mais", "me", "même", "mes", "moi", "mon", "ne",
+    "nos", "notre", "nous", "on", "ou", "par", "pas", "pour", "qu", "que", "qui", "sa", "se", "ses",
+    "son", "sur", "ta", "te", "tes", "toi", "ton", "tu", "un", "une", "vos", "votre", "vous", "c",
+    "d", "j", "l", "à", "m", "n", "s", "t", "y", "été", "est", "sont"
+];
+
+pub const GERMAN: &[&str] = &[
+    "aber", "als", "am", "an", "auch", "auf", "aus", "bei", "bin", "bis", "da", "das", "dass", "dem",
+    "den", "der", "des", "die", "du", "ein", "eine", "einem", "einen", "einer", "eines", "er", "es",
+    "für", "hat", "ich", "ihr", "im", "in", "ist", "mit", "nach", "nicht", "noch", "oder", "sie",
+    "sind", "so", "und", "uns", "von", "vor", "war", "was", "wie", "wir", "zu", "zum", "zur"
+];
+
+pub const SPANISH: &[&str] = &[
+    "a", "al", "como", "con", "de", "del", "el", "en", "es", "esta", "este", "ha", "la", "las", "le",
+    "lo", "los", "mas", "me", "mi", "no", "nos", "o", "para", "pero", "por", "que", "se", "si", "sin",
+    "su", "sus", "te", "tu", "un", "una", "uno", "unos", "y", "ya", "yo"
+];
+
+/// The built-in list for `language`.
+pub fn words(language: Language) -> &'static [&'static str] {
+    match language {
+        Language::English => ENGLISH,
+        Language::French => FRENCH,
+        Language::German => GERMAN,
+        Language::Spanish => SPANISH
+    }
+}
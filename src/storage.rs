@@ -1,41 +1,91 @@
-use std::{fs::OpenOptions, io::{Error, Read}};
+use std::io::{Error, Read};
 
 use super::page::PageCacheManager;
-use std::{collections::HashMap, hash::Hash, io::{self, ErrorKind}, path::{Path, PathBuf}};
-use super::journal::WAL;
+use std::{collections::HashMap, fs::File, io::{self, ErrorKind}, path::{Path, PathBuf}};
+use super::journal::{self, WAL};
+use super::atomic_write;
 
 #[derive(Debug)]
 pub struct SegmentStore { 
     page_cache: PageCacheManager,
     term_offsets : HashMap<String, Vec<(usize, usize)>>,
-    wal: WAL
+    wal: WAL,
+    path: PathBuf,
+    journal_dir: PathBuf,
+    page_size: usize,
+    cap: usize
 }
 
 impl SegmentStore { 
-    pub fn new(path: &Path, page_size: usize, cap: usize) -> io::Result<Self> { 
-    
-        let mut wal = WAL::new(4096, 0)?;
+    /// Opens the segments at `path`, with the WAL kept under `journal_dir`.
+    pub fn new(path: &Path, journal_dir: &Path, page_size: usize, cap: usize) -> io::Result<Self> { 
+        let mut storage = Self::open(path, journal_dir, page_size, cap)?;
+        // a rewrite that did not get to finish is done over
+        let pending = Self::pending_path(path);
+        if std::fs::exists(&pending)? { 
+            let mut buf = Vec::new();
+            File::open(&pending)?.read_to_end(&mut buf)?;
+            let (chunks, _): (Vec<(String, Vec<u8>)>, _) = bincode::decode_from_slice(&buf, bincode::config::standard())
+                .map_err(|err| Error::new(ErrorKind::InvalidData, format!("can not decode pending rewrite: {err}")))?;
+            storage.replace(&chunks)?;
+            std::fs::remove_file(pending)?;
+        }
+        Ok(storage)
+    }
+
+    fn open(path: &Path, journal_dir: &Path, page_size: usize, cap: usize) -> io::Result<Self> { 
+        let mut wal = WAL::open(journal_dir, journal::LOG_FILE_SIZE, 0)?;
         let records = wal.read_records();
         let last_page_offset_and_size = wal.find_last_page_last_written_offset();
         let page_cache = PageCacheManager::new(path, page_size, cap, last_page_offset_and_size)?;
         
         
         let mut term_offsets: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
-        for record in records { 
-            let each_record_str:  Vec<String> = record.split(",").map(|s| s.to_string()).collect();
-            let offset : usize = each_record_str[1].parse().unwrap();
-            let size: usize = each_record_str[2].parse().unwrap();
-            term_offsets.entry(each_record_str[0].clone()).or_default().push((offset, size));
+        for (term, offset, size) in records.iter().filter_map(|record| journal::parse_record(record)) { 
+            term_offsets.entry(term.into_owned()).or_default().push((offset, size));
         }
         let storage = Self { 
             page_cache,
             term_offsets,
-            wal
+            wal,
+            path: path.to_path_buf(),
+            journal_dir: journal_dir.to_path_buf(),
+            page_size,
+            cap
         };
         //println!("storage :{storage:?}");
         Ok(storage)
     }
 
+    fn pending_path(path: &Path) -> PathBuf { 
+        path.with_extension("pending")
+    }
+
+    /// Replaces the segments and the journal with ones holding only `chunks`,
+    /// given as `(term, encoded postings)`. The chunks are saved aside first,
+    /// so a crash part way through is finished by the next `new`.
+    pub fn rewrite(&mut self, chunks: &[(String, Vec<u8>)]) -> io::Result<()> { 
+        let bytes = bincode::encode_to_vec(chunks, bincode::config::standard())
+            .map_err(|err| Error::new(ErrorKind::InvalidData, format!("can not encode pending rewrite: {err}")))?;
+        let pending = Self::pending_path(&self.path);
+        atomic_write(&pending, &bytes)?;
+        self.replace(chunks)?;
+        std::fs::remove_file(pending)
+    }
+
+    fn replace(&mut self, chunks: &[(String, Vec<u8>)]) -> io::Result<()> { 
+        self.wal.destroy()?;
+        let (path, journal_dir) = (self.path.clone(), self.journal_dir.clone());
+        if std::fs::exists(&path)? { 
+            std::fs::remove_file(&path)?;
+        }
+        *self = Self::open(&path, &journal_dir, self.page_size, self.cap)?;
+        for (term, chunk) in chunks { 
+            self.write(term.clone(), chunk)?;
+        }
+        self.sync()
+    }
+
     pub fn write(&mut self, term: String, data: &[u8]) -> Result<(usize, usize), std::io::Error>{ 
         if let Some((offset, page_cache_offset, size)) = self.write_bytes(data) { 
             self.page_cache.update_last_page_offset(page_cache_offset, size);
@@ -52,9 +102,8 @@ impl SegmentStore {
         self.term_offsets.entry(term.clone()).or_default().push( (offset, size));
         // also update the value of last_page offset and size in page cache manager
         self.page_cache.update_last_page_offset(offset, size);
-        let record = format!("{},{},{}", term.clone(), offset, size);
-        println!("logging to journal");
-        let _ = self.wal.log(record)?;
+        let record = format!("{},{},{}", journal::escape_term(&term), offset, size);
+        self.wal.log(record)?;
         Ok((offset, size))
     }
 
@@ -74,6 +123,25 @@ impl SegmentStore {
         Err(Error::new(ErrorKind::NotFound, "term not found"))
     }
 
+    /// Every term that has postings in the segments.
+    pub fn terms(&self) -> impl Iterator<Item = &String> { 
+        self.term_offsets.keys()
+    }
+
+    pub fn contains(&self, term: &str) -> bool { 
+        self.term_offsets.contains_key(term)
+    }
+
+    /// Number of postings chunks written across all terms.
+    pub fn entries(&self) -> usize { 
+        self.term_offsets.values().map(Vec::len).sum()
+    }
+
+    /// Number of postings chunks written for `term`.
+    pub fn chunks(&self, term: &str) -> usize { 
+        self.term_offsets.get(term).map(Vec::len).unwrap_or(0)
+    }
+
     pub fn sync(&mut self) -> io::Result<()> { 
         self.page_cache.flush_all()
     }
@@ -128,4 +196,62 @@ pub fn test_segment_store() {
     println!("dummy buf : {:?}", dummy_buf);
 }
 
-*/ 
\ No newline at end of file
+*/ 
+#[test]
+pub fn test_rewrite() { 
+    let dir = std::env::temp_dir().join(format!("rusterine-segments-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("index.seg");
+    let mut store = SegmentStore::new(&path, &dir, 4096, 16).unwrap();
+    store.write("rust".to_string(), &[1, 2, 3]).unwrap();
+    store.write("java".to_string(), &[4, 5]).unwrap();
+    store.rewrite(&[("rust".to_string(), vec![1, 2, 3])]).unwrap();
+    assert_eq!(store.read_bytes("rust".to_string()).unwrap(), vec![vec![1, 2, 3]]);
+    assert!(!store.contains("java"));
+    drop(store);
+
+    let mut store = SegmentStore::new(&path, &dir, 4096, 16).unwrap();
+    assert_eq!(store.read_bytes("rust".to_string()).unwrap(), vec![vec![1, 2, 3]]);
+    assert!(!store.contains("java"));
+
+    // a rewrite cut short after saving its chunks is finished on the next open
+    let chunks = vec![("go".to_string(), vec![9u8, 9])];
+    std::fs::write(SegmentStore::pending_path(&path), bincode::encode_to_vec(&chunks, bincode::config::standard()).unwrap()).unwrap();
+    drop(store);
+    let mut store = SegmentStore::new(&path, &dir, 4096, 16).unwrap();
+    assert_eq!(store.read_bytes("go".to_string()).unwrap(), vec![vec![9, 9]]);
+    assert!(!store.contains("rust"));
+    assert!(!std::fs::exists(SegmentStore::pending_path(&path)).unwrap());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+pub fn test_many_pages() { 
+    let dir = std::env::temp_dir().join(format!("rusterine-pages-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("index.seg");
+    // more pages than the cache holds, with chunks that leave every page part empty
+    let chunk = |i: usize| vec![(i % 250) as u8 + 1; 700 + i % 300];
+    let mut store = SegmentStore::new(&path, &dir, 4096, 4).unwrap();
+    for i in 0..60 { 
+        store.write(format!("term{i}"), &chunk(i)).unwrap();
+    }
+    assert!(std::fs::metadata(&path).unwrap().len() > 8 * 4096);
+    for i in 0..60 { 
+        assert_eq!(store.read_bytes(format!("term{i}")).unwrap(), vec![chunk(i)]);
+    }
+    drop(store);
+
+    // reads after a reopen must not move where the next write goes
+    let mut store = SegmentStore::new(&path, &dir, 4096, 4).unwrap();
+    assert_eq!(store.read_bytes("term57".to_string()).unwrap(), vec![chunk(57)]);
+    for i in 60..80 { 
+        store.write(format!("term{i}"), &chunk(i)).unwrap();
+    }
+    for i in 0..80 { 
+        assert_eq!(store.read_bytes(format!("term{i}")).unwrap(), vec![chunk(i)]);
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
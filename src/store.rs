@@ -0,0 +1,296 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Error, ErrorKind, Write};
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+use bincode::{Decode, Encode};
+
+use super::{DocumentId, replace_file};
+use super::document::Document;
+
+/// Uncompressed bytes of field values collected before a block is written.
+pub const BLOCK_SIZE: usize = 16 * 1024;
+
+/// Starts every store file, followed by the format version as a `u32`.
+const MAGIC: &[u8; 4] = b"RDST";
+const VERSION: u32 = 2;
+const HEADER_LEN: u64 = 8;
+
+/// The documents of one block, in the order they were added.
+type Block = Vec<(DocumentId, StoredDocument)>;
+
+/// What the store keeps of a document: its stored fields, and the keys of
+/// every term it was indexed under so a delete can find them again.
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Default)]
+pub struct StoredDocument {
+    pub document: Document,
+    pub terms: Vec<String>
+}
+
+/// Keeps the stored fields of every document in LZ4 compressed blocks,
+/// appended to a single file after an 8 byte header.
+///
+/// A block is laid out as `[payload length: u32][count: u32][count document
+/// ids: u64][payload]`, all little endian, where the payload is the
+/// compressed bincode of the documents. The ids up front let `open` find
+/// every document by reading the headers only. Files without a header hold
+/// bare documents, without their terms, and are migrated by `open`.
+#[derive(Debug)]
+pub struct DocStore {
+    file: File,
+    // offset of the block holding each document
+    blocks: HashMap<DocumentId, u64>,
+    end: u64,
+    pending: Vec<(DocumentId, StoredDocument)>,
+    pending_size: usize,
+    // the block read last, by offset, as lookups tend to hit the same one
+    last_block: RefCell<Option<(u64, Block)>>
+}
+
+impl DocStore {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+        let mut len = file.metadata()?.len();
+        if len == 0 {
+            file.write_all(MAGIC)?;
+            file.write_all(&VERSION.to_le_bytes())?;
+            len = HEADER_LEN;
+        }
+        let mut header = [0u8; HEADER_LEN as usize];
+        if len < HEADER_LEN || file.read_exact_at(&mut header, 0).is_err() || &header[..4] != MAGIC {
+            Self::migrate(path, file, len)?;
+            return Self::open(path)
+        }
+        let version = u32::from_le_bytes(header[4..].try_into().unwrap());
+        if version != VERSION {
+            return Err(Error::new(ErrorKind::InvalidData, format!("unsupported document store version {version}")))
+        }
+        let (blocks, end) = Self::scan(&file, HEADER_LEN, len)?;
+        // a block cut short by a crash is dropped, the next one goes in its place
+        if end < len {
+            file.set_len(end)?;
+        }
+        Ok(Self { file, blocks, end, pending: Vec::new(), pending_size: 0, last_block: RefCell::new(None) })
+    }
+
+    /// Finds the block of every document in the blocks from `offset` on, and
+    /// where the last complete block ends.
+    fn scan(file: &File, mut offset: u64, len: u64) -> io::Result<(HashMap<DocumentId, u64>, u64)> {
+        let mut blocks = HashMap::new();
+        while let Some((payload_len, count)) = Self::header(file, offset, len) {
+            let block_len = 8 + count * 8 + payload_len;
+            if offset + block_len > len {
+                break
+            }
+            let mut ids = vec![0u8; count as usize * 8];
+            file.read_exact_at(&mut ids, offset + 8)?;
+            for id in ids.chunks_exact(8) {
+                blocks.insert(u64::from_le_bytes(id.try_into().unwrap()) as DocumentId, offset);
+            }
+            offset += block_len;
+        }
+        Ok((blocks, offset))
+    }
+
+    /// Rewrites a store of bare documents, as written before the header, in
+    /// the current format. Their terms are not known and left empty.
+    fn migrate(path: &Path, file: File, len: u64) -> io::Result<()> {
+        let (blocks, _) = Self::scan(&file, 0, len)?;
+        let legacy = Self { file, blocks, end: len, pending: Vec::new(), pending_size: 0, last_block: RefCell::new(None) };
+        let tmp_path = path.with_extension("tmp");
+        if std::fs::exists(&tmp_path)? {
+            std::fs::remove_file(&tmp_path)?;
+        }
+        let mut migrated = Self::open(&tmp_path)?;
+        for offset in legacy.offsets() {
+            let documents: Vec<(DocumentId, Document)> = legacy.read_block(offset)?;
+            for (doc_id, document) in documents {
+                migrated.add(doc_id, StoredDocument { document, terms: Vec::new() })?;
+            }
+        }
+        migrated.flush()?;
+        replace_file(&tmp_path, path)
+    }
+
+    /// Offsets of the blocks holding documents, in file order.
+    fn offsets(&self) -> Vec<u64> {
+        let mut offsets: Vec<u64> = self.blocks.values().copied().collect();
+        offsets.sort_unstable();
+        offsets.dedup();
+        offsets
+    }
+
+    /// Payload length and document count of the block at `offset`.
+    fn header(file: &File, offset: u64, len: u64) -> Option<(u64, u64)> {
+        let mut header = [0u8; 8];
+        if offset + 8 > len || file.read_exact_at(&mut header, offset).is_err() {
+            return None
+        }
+        let payload_len = u32::from_le_bytes(header[..4].try_into().unwrap());
+        let count = u32::from_le_bytes(header[4..].try_into().unwrap());
+        Some((payload_len as u64, count as u64))
+    }
+
+    /// Queues a document; it is written once a block's worth has been
+    /// collected or on `flush`.
+    pub fn add(&mut self, doc_id: DocumentId, document: StoredDocument) -> io::Result<()> {
+        self.pending_size += document.document.fields.iter().map(|(name, value)| name.len() + value.len()).sum::<usize>()
+            + document.terms.iter().map(String::len).sum::<usize>();
+        self.pending.push((doc_id, document));
+        if self.pending_size >= BLOCK_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Compresses the queued documents into a block and appends it.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(())
+        }
+        let encoded = bincode::encode_to_vec(&self.pending, bincode::config::standard())
+            .map_err(|err| Error::new(ErrorKind::InvalidData, format!("can not encode documents: {err}")))?;
+        let payload = lz4_flex::compress_prepend_size(&encoded);
+        let mut block = Vec::with_capacity(8 + self.pending.len() * 8 + payload.len());
+        block.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        block.extend_from_slice(&(self.pending.len() as u32).to_le_bytes());
+        for (doc_id, _) in &self.pending {
+            block.extend_from_slice(&(*doc_id as u64).to_le_bytes());
+        }
+        block.extend_from_slice(&payload);
+        self.file.write_all(&block)?;
+        self.file.sync_data()?;
+        for (doc_id, _) in self.pending.drain(..) {
+            self.blocks.insert(doc_id, self.end);
+        }
+        self.end += block.len() as u64;
+        self.pending_size = 0;
+        Ok(())
+    }
+
+    /// Whether the document is stored, written out or still queued.
+    pub fn contains(&self, doc_id: DocumentId) -> bool {
+        self.blocks.contains_key(&doc_id) || self.pending.iter().any(|(id, _)| *id == doc_id)
+    }
+
+    /// The stored fields of a document, `None` if it was never stored.
+    pub fn get(&self, doc_id: DocumentId) -> io::Result<Option<Document>> {
+        Ok(self.entry(doc_id)?.map(|stored| stored.document))
+    }
+
+    /// The keys of the terms a document was indexed under, empty if it was
+    /// never stored.
+    pub fn terms(&self, doc_id: DocumentId) -> io::Result<Vec<String>> {
+        Ok(self.entry(doc_id)?.map(|stored| stored.terms).unwrap_or_default())
+    }
+
+    fn entry(&self, doc_id: DocumentId) -> io::Result<Option<StoredDocument>> {
+        if let Some((_, document)) = self.pending.iter().find(|(id, _)| *id == doc_id) {
+            return Ok(Some(document.clone()))
+        }
+        let Some(&offset) = self.blocks.get(&doc_id) else {
+            return Ok(None)
+        };
+        let mut last_block = self.last_block.borrow_mut();
+        if last_block.as_ref().is_none_or(|(cached, _)| *cached != offset) {
+            *last_block = Some((offset, self.read_block(offset)?));
+        }
+        let (_, documents) = last_block.as_ref().unwrap();
+        Ok(documents.iter().find(|(id, _)| *id == doc_id).map(|(_, document)| document.clone()))
+    }
+
+    fn read_block<T: Decode<()>>(&self, offset: u64) -> io::Result<Vec<(DocumentId, T)>> {
+        let (payload_len, count) = Self::header(&self.file, offset, self.end)
+            .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, format!("block at {offset} is missing")))?;
+        let mut payload = vec![0u8; payload_len as usize];
+        self.file.read_exact_at(&mut payload, offset + 8 + count * 8)?;
+        let encoded = lz4_flex::decompress_size_prepended(&payload)
+            .map_err(|err| Error::new(ErrorKind::InvalidData, format!("can not decompress block at {offset}: {err}")))?;
+        bincode::decode_from_slice(&encoded, bincode::config::standard())
+            .map(|(documents, _)| documents)
+            .map_err(|err| Error::new(ErrorKind::InvalidData, format!("can not decode block at {offset}: {err}")))
+    }
+
+    /// Rewrites the store at `path` with only the documents `keep` accepts,
+    /// into a temporary file renamed over `path` once it is complete.
+    pub fn retain(&mut self, path: &Path, keep: impl Fn(DocumentId) -> bool) -> io::Result<()> {
+        self.rewrite(path, |doc_id, document| keep(doc_id).then_some(document))
+    }
+
+    /// Rewrites the store at `path` with every document passed through `f`,
+    /// which drops it by returning `None`. The new store goes to a temporary
+    /// file renamed over `path` once it is complete.
+    pub fn rewrite(&mut self, path: &Path, f: impl Fn(DocumentId, StoredDocument) -> Option<StoredDocument>) -> io::Result<()> {
+        self.flush()?;
+        let tmp_path = path.with_extension("tmp");
+        if std::fs::exists(&tmp_path)? {
+            std::fs::remove_file(&tmp_path)?;
+        }
+        let mut rewritten = Self::open(&tmp_path)?;
+        for offset in self.offsets() {
+            for (doc_id, document) in self.read_block(offset)? {
+                // a document stored twice only counts in the block it was last written to
+                if self.blocks.get(&doc_id) == Some(&offset) && let Some(document) = f(doc_id, document) {
+                    rewritten.add(doc_id, document)?;
+                }
+            }
+        }
+        rewritten.flush()?;
+        replace_file(&tmp_path, path)?;
+        *self = rewritten;
+        Ok(())
+    }
+}
+
+#[test]
+pub fn test_doc_store() {
+    let path = std::env::temp_dir().join(format!("rusterine-store-{}.store", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut store = DocStore::open(&path).unwrap();
+    let big = "Rust is a systems programming language. ".repeat(500);
+    let stored = |document: Document| StoredDocument { document, terms: vec!["rust".to_string()] };
+    store.add(0, stored(Document::new("a").field("content", "small"))).unwrap();
+    store.add(1, stored(Document::new("b").field("content", big.as_str()))).unwrap();
+    store.add(2, stored(Document::new("c").field("title", "pending"))).unwrap();
+    // the first two filled a block, which compresses well
+    assert!(std::fs::metadata(&path).unwrap().len() < big.len() as u64 / 4);
+    assert_eq!(store.get(2).unwrap(), Some(Document::new("c").field("title", "pending")));
+    store.flush().unwrap();
+
+    let reopened = DocStore::open(&path).unwrap();
+    assert_eq!(reopened.get(0).unwrap(), Some(Document::new("a").field("content", "small")));
+    assert_eq!(reopened.get(1).unwrap().unwrap().values("content").next(), Some(big.as_str()));
+    // both came out of the block decoded for the first
+    assert!(reopened.last_block.borrow().as_ref().is_some_and(|(offset, documents)| *offset == HEADER_LEN && documents.len() == 2));
+    assert_eq!(reopened.get(2).unwrap().unwrap().path, Path::new("c"));
+    assert_eq!(reopened.get(3).unwrap(), None);
+    assert!(reopened.contains(2) && !reopened.contains(3));
+    assert_eq!(reopened.terms(0).unwrap(), vec!["rust".to_string()]);
+
+    let mut reopened = reopened;
+    reopened.retain(&path, |doc_id| doc_id != 1).unwrap();
+    assert!(std::fs::metadata(&path).unwrap().len() < 200);
+    let compacted = DocStore::open(&path).unwrap();
+    assert_eq!(compacted.get(1).unwrap(), None);
+    assert_eq!(compacted.get(2).unwrap().unwrap().path, Path::new("c"));
+
+    // a store from before the header holds bare documents
+    let documents = vec![(7 as DocumentId, Document::new("old").field("content", "kept"))];
+    let payload = lz4_flex::compress_prepend_size(&bincode::encode_to_vec(&documents, bincode::config::standard()).unwrap());
+    let mut legacy = Vec::new();
+    legacy.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    legacy.extend_from_slice(&1u32.to_le_bytes());
+    legacy.extend_from_slice(&7u64.to_le_bytes());
+    legacy.extend_from_slice(&payload);
+    std::fs::write(&path, legacy).unwrap();
+    let migrated = DocStore::open(&path).unwrap();
+    assert_eq!(migrated.get(7).unwrap(), Some(Document::new("old").field("content", "kept")));
+    assert!(migrated.terms(7).unwrap().is_empty());
+    assert!(DocStore::open(&path).unwrap().get(7).unwrap().is_some());
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,129 @@
+//! Synonym rules in the Solr file format, one rule per line:
+//!
+//! ```text
+//! # equivalent terms, each one expands to all of them
+//! car, automobile, auto
+//! # explicit mapping, the left side is replaced by the right side
+//! k8s => kubernetes
+//! usa, united states of america => united states
+//! ```
+//!
+//! Entries may span several words. They are split on whitespace and matched
+//! against the token text as written, so the filter using them should come
+//! after lowercasing and before stemming.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default)]
+pub struct SynonymMap {
+    rules: HashMap<Vec<String>, Vec<Vec<String>>>,
+    longest: usize
+}
+
+impl SynonymMap {
+    /// Parses rules. With `expand` off, the terms of an equivalence line all
+    /// map to the first one instead of to each other.
+    pub fn parse(text: &str, expand: bool) -> io::Result<Self> {
+        let mut map = SynonymMap::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+            let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("synonyms line {}: {message}", number + 1));
+            let entries = |side: &str| -> io::Result<Vec<Vec<String>>> {
+                side.split(',')
+                    .map(|entry| {
+                        let words: Vec<String> = entry.split_whitespace().map(str::to_string).collect();
+                        if words.is_empty() { Err(invalid("empty entry")) } else { Ok(words) }
+                    })
+                    .collect()
+            };
+            match line.split_once("=>") {
+                Some((from, to)) => {
+                    let to = entries(to)?;
+                    for from in entries(from)? {
+                        map.add(from, &to);
+                    }
+                },
+                None => {
+                    let group = entries(line)?;
+                    let to = if expand { group.clone() } else { vec![group[0].clone()] };
+                    for from in group {
+                        map.add(from, &to);
+                    }
+                }
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>, expand: bool) -> io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?, expand)
+    }
+
+    fn add(&mut self, from: Vec<String>, to: &[Vec<String>]) {
+        self.longest = self.longest.max(from.len());
+        let outputs = self.rules.entry(from).or_default();
+        for output in to {
+            if !outputs.contains(output) {
+                outputs.push(output.clone());
+            }
+        }
+    }
+
+    /// What the word sequence is rewritten to, if a rule starts with it.
+    pub fn get(&self, words: &[String]) -> Option<&[Vec<String>]> {
+        self.rules.get(words).map(Vec::as_slice)
+    }
+
+    /// Number of words in the longest left-hand side.
+    pub fn longest(&self) -> usize {
+        self.longest
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+#[test]
+pub fn test_synonyms() {
+    use super::analysis::{Analyzer, SynonymFilter, TextAnalyzer};
+
+    let rules = "# vehicles\ncar, automobile\nk8s => kubernetes\nusa => united states\n";
+    let map = SynonymMap::parse(rules, true).unwrap();
+    let words = |text: &str| text.split(' ').map(str::to_string).collect::<Vec<String>>();
+    assert_eq!(map.get(&words("automobile")), Some(&[words("car"), words("automobile")][..]));
+    assert_eq!(map.get(&words("kubernetes")), None);
+    assert!(SynonymMap::parse("a, , b", true).is_err());
+
+    let analyzer = TextAnalyzer::default().filter(SynonymFilter::new(map));
+    let tokens = |text: &str| -> Vec<(String, usize)> {
+        analyzer.analyze(text).into_iter().map(|token| (token.text, token.position)).collect()
+    };
+    assert_eq!(tokens("Fast car"), vec![("fast".to_string(), 0), ("car".to_string(), 1), ("automobile".to_string(), 1)]);
+    assert_eq!(tokens("k8s cluster"), vec![("kubernetes".to_string(), 0), ("cluster".to_string(), 1)]);
+    // the two words of the synonym take two positions, pushing the rest back
+    assert_eq!(tokens("usa today"), vec![("united".to_string(), 0), ("states".to_string(), 1), ("today".to_string(), 2)]);
+
+    // the original word spans the positions of the words replacing it
+    let spans = |analyzer: &TextAnalyzer, text: &str| -> Vec<(String, usize, usize)> {
+        analyzer.analyze(text).into_iter().map(|token| (token.text, token.position, token.length)).collect()
+    };
+    let preserving = TextAnalyzer::default().filter(SynonymFilter::new(SynonymMap::parse(rules, true).unwrap()).preserve_original(true));
+    assert_eq!(spans(&preserving, "usa today"), vec![
+        ("usa".to_string(), 0, 2), ("united".to_string(), 0, 1), ("states".to_string(), 1, 1), ("today".to_string(), 2, 1)
+    ]);
+    assert_eq!(spans(&preserving, "fast car").len(), 3);
+
+    // without expanding, equivalent terms all become the first one
+    let path = std::env::temp_dir().join(format!("rusterine-synonyms-{}.txt", std::process::id()));
+    std::fs::write(&path, rules).unwrap();
+    let contracting = TextAnalyzer::default().filter(SynonymFilter::from_file(&path, false).unwrap());
+    let texts: Vec<String> = contracting.analyze("automobile").into_iter().map(|token| token.text).collect();
+    assert_eq!(texts, vec!["car".to_string()]);
+    let _ = std::fs::remove_file(&path);
+}